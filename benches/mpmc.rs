@@ -0,0 +1,84 @@
+//! Compares the shared deque guarded by a lock (`Queue::new()`)
+//! with the lock-free one (`Queue::lock_free()`),
+//! for 1, 4 and 16 threads serving one queue, fed by many producer threads.
+//!
+//! run: `cargo bench --bench mpmc`
+
+use std::{
+    sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use taskorch::{Pool, Queue, TaskBuildNew};
+
+const TASKS: usize = 200_000;
+const PRODUCERS: usize = 8;
+
+struct Report {
+    throughput: f64,
+    mean: Duration,
+    p50: Duration,
+    p99: Duration,
+}
+
+fn run(queue:Queue, workers:usize)->Report {
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&queue).unwrap();
+    for _ in 0..workers {
+        pool.spawn_thread_for(qid);
+    }
+    // the latency from submission to run of each task, in ns
+    let latencies = Arc::new((0..TASKS).map(|_|AtomicU64::new(0)).collect::<Vec<_>>());
+    let done = Arc::new(AtomicUsize::new(0));
+
+    let start = Instant::now();
+    let producers: Vec<_> = (0..PRODUCERS).map(|p|{
+        let submitter = pool.task_submitter(qid).unwrap();
+        let latencies = latencies.clone();
+        let done = done.clone();
+        thread::spawn(move||{
+            for i in (p..TASKS).step_by(PRODUCERS) {
+                let latencies = latencies.clone();
+                let done = done.clone();
+                let submitted = Instant::now();
+                submitter.submit((move||{
+                    latencies[i].store(submitted.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                    done.fetch_add(1, Ordering::Release);
+                }).into_task());
+            }
+        })
+    }).collect();
+    producers.into_iter().for_each(|p|p.join().unwrap());
+    while done.load(Ordering::Acquire) < TASKS {
+        thread::yield_now();
+    }
+    let elapsed = start.elapsed();
+
+    let submitter = pool.task_submitter(qid).unwrap();
+    for _ in 0..workers {
+        submitter.submit((||{}).into_exit_task());
+    }
+    pool.join();
+
+    let mut latencies: Vec<u64> = latencies.iter().map(|l|l.load(Ordering::Relaxed)).collect();
+    latencies.sort_unstable();
+    let at = |q:f64|Duration::from_nanos(latencies[((TASKS-1) as f64 * q) as usize]);
+    Report {
+        throughput: TASKS as f64 / elapsed.as_secs_f64(),
+        mean: Duration::from_nanos(latencies.iter().sum::<u64>() / TASKS as u64),
+        p50: at(0.5),
+        p99: at(0.99),
+    }
+}
+
+fn main() {
+    println!("{TASKS} tasks from {PRODUCERS} producers");
+    println!("{:<10} {:>8} {:>14} {:>12} {:>12} {:>12}", "queue", "workers", "tasks/s", "mean", "p50", "p99");
+    for workers in [1,4,16] {
+        for (name,queue) in [("locked",Queue::new()),("lock-free",Queue::lock_free())] {
+            let r = run(queue, workers);
+            println!("{:<10} {:>8} {:>14.0} {:>12?} {:>12?} {:>12?}", name, workers, r.throughput, r.mean, r.p50, r.p99);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! # `batch` module
+//!
+//! A [`GraphBatch`] submits a whole graph of tasks at once, see [`TaskSubmitter::batch()`].
+//!
+//! Submitted one by one, an upstream task may run and deliver its result
+//! before its downstream task is in the waitQueue, then the condition is dropped.
+//! A batch puts all its conditional tasks into the waitQueue first,
+//! and only then puts its tasks without conditions into the queue,
+//! all or none of them, with a single error report.
+
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    num::NonZeroUsize,
+    time::Instant,
+};
+
+use crate::{
+    cond::TaskId,
+    curry::CallOnce,
+    graph::{inputs, GraphReport, Input, Producer},
+    handle::{result_channel, ResultHandle},
+    queue::{PostDo, TaskDo, Waiting},
+    submitter::{Compilable, TaskInf},
+    task::{taskid_next, PsOf, TaskNeed},
+    TaskSubmitter,
+};
+
+/// Error returned by [`GraphBatch::submit()`], none of the tasks of the batch has been submitted.
+#[derive(Debug, PartialEq)]
+pub enum BatchError {
+    /// the pool has been shut down, see `Pool::shutdown()`.
+    PoolClosed,
+    /// the task ids added more than once to the batch, or already waiting in the waitQueue.
+    TaskIdConflicts(Vec<TaskId>),
+    /// the graph is mis-wired, see [`GraphBatch::validate()`].
+    Invalid(GraphReport),
+}
+
+enum Entry {
+    /// a task without conditions, put into the queue at last
+    Ready { compiled: (Box<TaskDo>,Box<PostDo>), not_before: Option<Instant>, producer: Option<Producer> },
+    /// a conditional task, put into the waitQueue first
+    Waiting { taskid: NonZeroUsize, waiting: Waiting, deadline: Option<Instant>, producer: Option<Producer>, inputs: Vec<Input> },
+}
+
+/// A graph of tasks submitted all at once, built by [`TaskSubmitter::batch()`].
+///
+/// The tasks are added as by [`TaskSubmitter::submit()`], their ids are assigned at once,
+/// so they can be bound to each other before anything runs.
+/// The tasks of a dropped batch are never submitted.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{Pool, Queue, TaskBuildNew};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+/// pool.spawn_thread_for(qid);
+///
+/// let mut batch = submitter.batch();
+/// let (sum, handle) = batch.add_with_handle((|a:i32,b:i32|a+b).into_task());
+/// // the inputs may run as soon as they are in the queue, the sum is already waiting then
+/// batch.add((||1).into_task().bind_to(sum.input_ca::<0>()));
+/// batch.add((||2).into_task().bind_to(sum.input_ca::<1>()));
+/// batch.submit().unwrap();
+///
+/// assert_eq!(handle.wait(), Ok(3));
+/// pool.shutdown(taskorch::Shutdown::Drain);
+/// ```
+pub struct GraphBatch {
+    submitter: TaskSubmitter,
+    entries: Vec<Entry>,
+    /// the ids of the conditional tasks added
+    ids: HashSet<NonZeroUsize>,
+    /// the ids added more than once
+    duplicates: Vec<TaskId>,
+}
+
+impl GraphBatch {
+    pub(crate) fn new(submitter:TaskSubmitter)->Self {
+        Self { submitter, entries: Vec::new(), ids: HashSet::new(), duplicates: Vec::new() }
+    }
+
+    /// Adds a task to the batch, a conditional one is given an id if it has none.
+    ///
+    /// # returns
+    /// * `TaskInf` - the id and the input addresses of the task, to bind the other tasks to.
+    #[allow(private_bounds)]
+    pub fn add<C,MapFn,MapR,ToFn>(&mut self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->TaskInf<C::InputPs>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+    {
+        self.add_keep(taskneed, |_:&C::R|{})
+    }
+
+    /// Same as [`add()`](Self::add), and returns a handle to the result of the task,
+    /// see [`TaskSubmitter::submit_with_handle()`].
+    #[allow(private_bounds)]
+    pub fn add_with_handle<C,MapFn,MapR,ToFn>(&mut self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->(TaskInf<C::InputPs>,ResultHandle<C::R>)
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        C::R: 'static + Clone + Send,
+    {
+        let (sender,handle) = result_channel();
+        let inf = self.add_keep(taskneed, move |r:&C::R|sender.send(r.clone()));
+        (inf,handle)
+    }
+
+    /// the count of the tasks added
+    pub fn len(&self)->usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks the graph made of the tasks of the batch, and of the tasks already submitted,
+    /// see [`Pool::validate()`](crate::Pool::validate).
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildNew, GraphIssue, BatchError};
+    /// # let mut pool = Pool::new();
+    /// # let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// let mut batch = submitter.batch();
+    /// let a = batch.add((|a:i32|a).into_task());
+    /// let b = batch.add((|b:i32|b).into_task().bind_to(a.input_ca::<0>()));
+    /// batch.add((||"b").into_task().bind_to((b.taskid(),taskorch::Section::Input,taskorch::ArgIdx::AI0).into()));
+    ///
+    /// let report = batch.validate();
+    /// assert_eq!(report.issues()[0], GraphIssue::TypeMismatch { from: taskorch::TaskId::NONE, to: b.taskid(), argidx: 0, expected: "i32", found: "&str" });
+    /// // `a` is waiting for `b` which is waiting for nothing
+    /// assert!(matches!(batch.submit(), Err(BatchError::Invalid(_))));
+    /// ```
+    pub fn validate(&self)->GraphReport {
+        let mut graph = self.submitter.c1map.graph();
+        graph.focus();
+        for entry in &self.entries {
+            let producer = match entry {
+                Entry::Ready { producer, .. } => producer,
+                Entry::Waiting { taskid, producer, inputs, .. } => {
+                    graph.add_node(*taskid, inputs.clone());
+                    producer
+                }
+            };
+            if let Some(producer) = producer {
+                graph.add_bindings(producer.from, producer.targets.clone());
+            }
+        }
+        graph.validate()
+    }
+
+    /// Submits all the tasks of the batch, or none of them.
+    ///
+    /// The graph is validated first, see [`validate()`](Self::validate).
+    /// The conditional tasks are put into the waitQueue first, then the others into the queue.
+    /// The tasks without conditions are admitted beyond the capacity of a bounded queue,
+    /// so the batch never fails halfway.
+    ///
+    /// # returns
+    /// * `Ok(())` - all the tasks have been submitted.
+    /// * `Err(BatchError)` - none of them, they are dropped and their result handles report `Abandoned`.
+    pub fn submit(self)->Result<(),BatchError> {
+        if self.submitter.is_closed() {
+            error!("a batch of {} tasks is rejected: the pool has been shut down.", self.entries.len());
+            return Err(BatchError::PoolClosed);
+        }
+        if !self.duplicates.is_empty() {
+            error!("a batch of {} tasks is rejected: task ids {:?} are added more than once.", self.entries.len(), self.duplicates);
+            return Err(BatchError::TaskIdConflicts(self.duplicates));
+        }
+        let waiting: Vec<_> = self.ids.iter()
+            .map(|taskid|TaskId(Some(*taskid)))
+            .filter(|taskid|self.submitter.c1map.check(*taskid).is_some())
+            .collect();
+        if !waiting.is_empty() {
+            error!("a batch of {} tasks is rejected: task ids {waiting:?} are already waiting.", self.entries.len());
+            return Err(BatchError::TaskIdConflicts(waiting));
+        }
+        let report = self.validate();
+        if !report.is_ok() {
+            error!("a batch of {} tasks is rejected: {report:?}", self.entries.len());
+            return Err(BatchError::Invalid(report));
+        }
+        let Self { submitter, entries, .. } = self;
+        let _count = entries.len();
+        let mut waitings = Vec::new();
+        let mut schedules = Vec::new();
+        let mut readies = Vec::new();
+        let producers = submitter.c1map.producers();
+        for entry in entries {
+            // unregistered again if the tasks are dropped
+            match entry {
+                Entry::Waiting { taskid, waiting, deadline, producer, .. } => {
+                    producers.register(producer);
+                    schedules.push((taskid,deadline,waiting.not_before));
+                    waitings.push((taskid,waiting));
+                }
+                Entry::Ready { compiled, not_before, producer } => {
+                    producers.register(producer);
+                    readies.push((compiled,not_before));
+                }
+            }
+        }
+        if let Err(conflicts) = submitter.c1map.insert_all(waitings) {
+            error!("a batch of {_count} tasks is rejected: task ids {conflicts:?} are already waiting.");
+            return Err(BatchError::TaskIdConflicts(conflicts));
+        }
+        let observers = submitter.c1map.observers();
+        for (taskid,deadline,not_before) in schedules {
+            submitter.schedule_expiry(taskid, deadline);
+            submitter.schedule_release(taskid, not_before);
+            observers.notify(|o|o.submitted(TaskId(Some(taskid)), submitter.qid, true));
+        }
+        // notified before they are put in, all of them can not fail any more.
+        for (compiled,_) in &readies {
+            observers.notify(|o|o.submitted(compiled.0.id(), submitter.qid, false));
+        }
+        for (compiled,not_before) in readies {
+            match not_before {
+                Some(at) if at > Instant::now() => {
+                    let q = submitter.queue.clone();
+                    submitter.timer.schedule(at, Box::new(move||q.add_boxtask(compiled)));
+                }
+                _ => submitter.queue.add_boxtask(compiled),
+            }
+        }
+        debug!("a batch of {_count} tasks added into Q#{}", submitter.qid);
+        Ok(())
+    }
+
+    /// the implementation of `add()`, the same as `TaskSubmitter::submit_within()`
+    /// except that the task is kept in the batch.
+    #[allow(private_bounds)]
+    fn add_keep<C,MapFn,MapR,ToFn,Keep>(&mut self,mut taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>,keep:Keep)->TaskInf<C::InputPs>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        Keep: FnOnce(&C::R) + Send + 'static,
+    {
+        if 0 == taskneed.task.currier.count() {
+            let taskid = taskneed.task.id;
+            if taskneed.attr.expiry.is_some() {
+                warn!("Ignore the deadline of task#{taskid:?}: no conditions to wait for.");
+            }
+            let not_before = taskneed.attr.not_before.take();
+            let home = self.submitter.home(None);
+            let (compiled,producer) = self.submitter.compile(taskneed, keep, home);
+            self.entries.push(Entry::Ready { compiled, not_before, producer });
+            return TaskInf::new(taskid);
+        }
+        if taskneed.id().0.is_none() {
+            taskneed.task.id = taskid_next();
+        }
+        let TaskId(Some(taskid)) = taskneed.task.id else {
+            unreachable!("task id has been assigned above");
+        };
+        if !self.ids.insert(taskid) {
+            warn!("cond-task#{taskid:?} is added more than once to the batch.");
+            self.duplicates.push(TaskId(Some(taskid)));
+        }
+        let attr = taskneed.attr.take_waiting();
+        let home = self.submitter.home(attr.release.as_ref());
+        let (compiled,producer) = self.submitter.compile(taskneed, keep, home);
+        let (mut waiting,deadline) = self.submitter.waiting(compiled, attr);
+        let inputs = inputs(&mut *waiting.task);
+        self.entries.push(Entry::Waiting { taskid, waiting, deadline, producer, inputs });
+        TaskInf::new(TaskId(Some(taskid)))
+    }
+}
+
+impl Debug for GraphBatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"GraphBatch{{Q#{},tasks:{}}}",self.submitter.qid,self.entries.len())
+    }
+}
+
+#[test]
+fn test_graph_batch() {
+    use crate::{Pool, Queue, ResultError, TaskBuildNew};
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    pool.spawn_thread_for(qid);
+
+    // a diamond, the sources are added first
+    let mut batch = submitter.batch();
+    let id_left = taskid_next();
+    let id_right = taskid_next();
+    let (join,handle) = batch.add_with_handle((|a:i32,b:i32|a*b).into_task());
+    let left = (|a:i32|a+1,id_left).into_task().bind_to(join.input_ca::<0>());
+    let right = (|a:i32|a+2,id_right).into_task().bind_to(join.input_ca::<1>());
+    let source = (||3).into_task().map_tuple_with(|a:i32|(a,a))
+        .bind_all_to((left.input_ca::<0>(), right.input_ca::<0>()));
+    batch.add(source);
+    batch.add(left);
+    batch.add(right);
+    assert_eq!(batch.len(), 4);
+    batch.submit().unwrap();
+    assert_eq!(handle.wait(), Ok(20));
+
+    // all or nothing
+    let waiting = submitter.submit((|_:i32|{}).into_task()).take().taskid();
+    let mut batch = submitter.batch();
+    let (_,ready) = batch.add_with_handle((||1).into_task());
+    let (_,fresh) = batch.add_with_handle((|a:i32|a).into_task());
+    batch.add((|_:i32|{},waiting).into_task());
+    assert_eq!(batch.submit(), Err(BatchError::TaskIdConflicts(vec![waiting])));
+    assert_eq!(ready.wait(), Err(ResultError::Abandoned));
+    assert_eq!(fresh.wait(), Err(ResultError::Abandoned));
+
+    let mut batch = submitter.batch();
+    let id = taskid_next();
+    batch.add((|_:i32|{},id).into_task());
+    batch.add((|_:u8|{},id).into_task());
+    assert_eq!(batch.submit(), Err(BatchError::TaskIdConflicts(vec![id])));
+    assert!(submitter.batch().submit().is_ok());
+    pool.shutdown(crate::Shutdown::Drain);
+}
@@ -231,7 +231,7 @@ impl From<usize> for TaskId {
             panic!("TaskId cannot be zero");
         }
         #[cfg(not(debug_assertions))]
-        if crate::log::LEVEL as usize >= crate::log::Level::Warn as usize && id == 0 {
+        if crate::log::LEVEL as usize >= crate::log::LogLevel::Warn as usize && id == 0 {
             warn!("TaskId::new() the input id is zero, is not avaiable!");
         }
         Self::new(id)
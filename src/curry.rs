@@ -12,6 +12,23 @@ where C:TupleOpt {
     r: PhantomData<R>,
 }
 
+/// copies the closure with the params set so far, to run it again, see `TaskNeed::retry()`.
+impl<F:Clone,C:TupleOpt,R> Clone for Currier<F,C,R>
+    where C::Opt: Clone
+{
+    fn clone(&self) -> Self {
+        Self { f: self.f.clone(), c: self.c.clone(), r: PhantomData }
+    }
+}
+
+#[allow(private_bounds)]
+impl<F,C:TupleOpt,R> Currier<F,C,R> {
+    /// replaces the closure, keeping the params set so far, see `scope()`.
+    pub(crate) fn map_fn<G>(self, g:impl FnOnce(F)->G)->Currier<G,C,R> {
+        Currier { f: g(self.f), c: self.c, r: PhantomData }
+    }
+}
+
 impl<F,R> From<F> for Currier<F,(),R>
     where
     F:FnOnce()->R
@@ -130,6 +147,13 @@ pub(crate) trait CallParam {
     fn set(&mut self, i:usize, value: &dyn Any)->bool;
     fn typename(&self, i:usize)->&'static str;
     fn is_full(&self)->bool;
+    /// the count of the params
+    fn arity(&self)->usize;
+    /// whether the i-th param has been set
+    fn is_set(&self, i:usize)->bool;
+    /// sets the params not set yet from `values`, which must be the tuple of all params.
+    /// returns false if the type of `values` is not the params tuple.
+    fn fill(&mut self, values: &dyn Any)->bool;
 }
 
 /// Fn()->R
@@ -455,6 +479,21 @@ impl<F,P1:Clone+'static,R> CallParam for Currier<F,(P1,),R>
     fn is_full(&self)->bool {
         self.c.0.is_some()
     }
+    fn arity(&self)->usize {
+        1
+    }
+    fn is_set(&self, i:usize)->bool {
+        i == 0 && self.c.0.is_some()
+    }
+    fn fill(&mut self, values: &dyn Any)->bool {
+        let Some(values) = values.downcast_ref::<(P1,)>() else {
+            return false;
+        };
+        if self.c.0.is_none() {
+            self.c.0 = Some(values.0.clone());
+        }
+        true
+    }
 }
 
 
@@ -657,6 +696,28 @@ macro_rules! impl_currier_call {
             fn is_full(&self)->bool {
                 $(self.c.$i.is_some()) &&+
             }
+            fn arity(&self)->usize {
+                [$($i),+].len()
+            }
+            fn is_set(&self, i:usize)->bool {
+                match i {
+                    $(
+                    $i => self.c.$i.is_some(),
+                    )+
+                    _ => false
+                }
+            }
+            fn fill(&mut self, values: &dyn Any)->bool {
+                let Some(values) = values.downcast_ref::<($($P,)+)>() else {
+                    return false;
+                };
+                $(
+                if self.c.$i.is_none() {
+                    self.c.$i = Some(values.$i.clone());
+                }
+                )+
+                true
+            }
         }
     };
 }
@@ -708,5 +769,18 @@ mod test_2 {
         c.as_param_mut().unwrap().set(1, &4);
         c.call_once();
     }
+
+    #[test]
+    fn test_fill() {
+        let mut c = Currier::from(|a:i32,b:&str|format!("{a}{b}"));
+        let p = c.as_param_mut().unwrap();
+        assert_eq!(p.arity(), 2);
+        p.set(1, &"x");
+        assert!(!p.is_set(0) && p.is_set(1));
+        assert!(!p.fill(&(0i32,)));
+        assert!(p.fill(&(7i32,"y")));
+        assert!(p.is_full());
+        assert_eq!(c.call_once(), "7x");
+    }
 }
 
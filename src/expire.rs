@@ -0,0 +1,83 @@
+//! # `expire` module
+//!
+//! A conditional task waits until all its conditions arrive, which may never happen,
+//! e.g. its upstream task panicked or was never submitted.
+//!
+//! A deadline can be set on the task at build time, see [`TaskNeed::expire_after()`](crate::TaskNeed::expire_after),
+//! once it is reached and the task is still waiting, the task is removed from the waitQueue,
+//! and one of the following policies is applied:
+//! - drop the task
+//! - run a fallback closure instead, see [`TaskNeed::expire_after_or_else()`](crate::TaskNeed::expire_after_or_else)
+//! - fill the missing conditions with their default values and run the task,
+//!   see [`TaskNeed::expire_after_or_default()`](crate::TaskNeed::expire_after_or_default)
+//!
+//! The deadlines are enforced by the timer thread of the `Pool`,
+//! and each expiration is reported to the handler registered by [`Pool::on_expire()`](crate::Pool::on_expire).
+
+use std::{any::Any, fmt::Debug, time::Duration};
+
+use crate::{cond::TaskId, fault::Report};
+
+pub(crate) type Fallback = dyn FnOnce(TaskId) + Send;
+
+/// What to do with a task when its deadline is reached.
+pub(crate) enum ExpirePolicy {
+    Drop,
+    Fallback(Box<Fallback>),
+    /// the tuple of all the params with default values
+    Defaults(Box<dyn Any + Send>),
+}
+
+pub(crate) struct Expiry {
+    pub(crate) after: Duration,
+    pub(crate) policy: ExpirePolicy,
+}
+
+/// The action taken on an expired task.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExpireAction {
+    /// The task was dropped.
+    Dropped,
+    /// The fallback closure was put into the queue instead of the task.
+    Fallback,
+    /// The missing conditions were filled with default values and the task was put into the queue.
+    Defaulted,
+}
+
+/// Describes a conditional task whose deadline was reached before all its conditions arrived.
+pub struct TaskExpired {
+    taskid: TaskId,
+    qid: usize,
+    missing: Vec<u8>,
+    action: ExpireAction,
+}
+
+impl TaskExpired {
+    pub(crate) fn new(taskid:TaskId, qid:usize, missing:Vec<u8>, action:ExpireAction)->Self {
+        Self { taskid, qid, missing, action }
+    }
+    /// the id of the expired task
+    pub fn taskid(&self)->TaskId {
+        self.taskid
+    }
+    /// the ID of the queue the task was submitted to
+    pub fn qid(&self)->usize {
+        self.qid
+    }
+    /// the indexes of the conditions which never arrived
+    pub fn missing(&self)->&[u8] {
+        &self.missing
+    }
+    /// the action taken on the task
+    pub fn action(&self)->ExpireAction {
+        self.action
+    }
+}
+
+impl Debug for TaskExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"TaskExpired{{{:?},Q#{},missing{:?},{:?}}}",self.taskid,self.qid,self.missing,self.action)
+    }
+}
+
+pub(crate) type ExpireReport = Report<TaskExpired>;
@@ -0,0 +1,278 @@
+//! # `export` module
+//!
+//! Renders the task graph of a pool as Graphviz DOT or Mermaid text, see [`Pool::export_graph()`](crate::Pool::export_graph).
+//!
+//! The nodes are the tasks waiting in the waitQueue and the queued ones bound to others, each one with its id,
+//! kind, queue and the types of its inputs; the edges go from a task to the inputs it is bound to.
+//! A queued task bound to none is not shown, the pool does not keep track of it.
+//! A binding to a task which is not known any more, e.g. it has been run or canceled, ends at a `missing` node.
+//!
+//! Annotated with the live state, the waiting tasks show which conditions have been filled,
+//! and the latest released conditional tasks which have been run show as `done`.
+//! The bindings of the tasks run are not known any more, the inputs they filled show what they delivered.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    num::NonZeroUsize,
+};
+
+use crate::graph::{Input, Producer};
+use crate::task::Kind;
+use crate::cond::TaskId;
+
+/// A node of the graph, a task with an id, or a task without id numbered by its submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Task(NonZeroUsize),
+    Anonymous(u64),
+}
+
+impl Key {
+    fn name(&self)->String {
+        match self {
+            Key::Task(id) => format!("t{id}"),
+            Key::Anonymous(serial) => format!("a{serial}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Waiting,
+    Queued,
+    Done,
+}
+
+struct Node {
+    kind: Option<Kind>,
+    qid: Option<usize>,
+    /// the type of each input, and whether it has been filled if the task is waiting
+    inputs: Vec<(&'static str,Option<bool>)>,
+    state: State,
+}
+
+struct Edge {
+    from: Key,
+    to: Key,
+    input: bool,
+    argidx: u8,
+}
+
+/// A snapshot of the task graph of a pool, to be rendered as DOT or Mermaid text.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{Pool, Queue, TaskBuildNew};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+///
+/// let sum = submitter.submit((|a:i32,b:i32|a+b).into_task()).take();
+/// submitter.submit((||1).into_task().bind_to(sum.input_ca::<0>()));
+///
+/// let graph = pool.export_graph().with_state(true);
+/// // e.g. piped to `dot -Tsvg`, or pasted into a `mermaid` code block of a markdown file
+/// println!("{}", graph.to_dot());
+/// println!("{}", graph.to_mermaid());
+/// ```
+pub struct GraphExport {
+    nodes: BTreeMap<Key,Node>,
+    edges: Vec<Edge>,
+    state: bool,
+}
+
+impl GraphExport {
+    /// the tasks not run yet which are bound to others, all queued until known as waiting.
+    pub(crate) fn new(producers:Vec<Producer>)->Self {
+        let mut nodes = BTreeMap::new();
+        let mut edges = Vec::new();
+        for Producer { serial, from, kind, qid, inputs, targets } in producers {
+            let from = match from {
+                TaskId(Some(id)) => Key::Task(id),
+                TaskId(None) => Key::Anonymous(serial),
+            };
+            let inputs = inputs.into_iter().map(|typename|(typename,None)).collect();
+            nodes.insert(from, Node { kind: Some(kind), qid: Some(qid), inputs, state: State::Queued });
+            for target in targets {
+                edges.push(Edge { from, to: Key::Task(target.to), input: target.input, argidx: target.argidx });
+            }
+        }
+        edges.sort_by_key(|edge|(edge.from,edge.to,edge.argidx));
+        Self { nodes, edges, state: false }
+    }
+
+    /// the task waits in the waitQueue for its inputs, to be released into the queue `qid`,
+    /// the one of its last producer if `None`.
+    pub(crate) fn waiting(&mut self, taskid:NonZeroUsize, kind:Kind, qid:Option<usize>, inputs:Vec<Input>) {
+        let inputs = inputs.into_iter().map(|input|(input.typename,Some(input.set))).collect();
+        self.nodes.insert(Key::Task(taskid), Node { kind: Some(kind), qid, inputs, state: State::Waiting });
+    }
+
+    /// the conditional task has been released, it is done if it is not queued any more.
+    pub(crate) fn released(&mut self, taskid:NonZeroUsize) {
+        self.nodes.entry(Key::Task(taskid))
+            .or_insert(Node { kind: None, qid: None, inputs: Vec::new(), state: State::Done });
+    }
+
+    /// whether to annotate the nodes with their live state, false by default:
+    /// `waiting` with the filled inputs checked, `queued`, or `done`.
+    pub fn with_state(mut self, yes:bool)->Self {
+        self.state = yes;
+        self
+    }
+
+    /// the nodes to render, sorted, `None` for the missing ones bound to.
+    fn visible(&self)->Vec<(Key,Option<&Node>)> {
+        let shown = |node:&&Node|self.state || node.state != State::Done;
+        let missing: BTreeSet<Key> = self.edges.iter()
+            .map(|edge|edge.to)
+            .filter(|to|self.nodes.get(to).filter(shown).is_none())
+            .collect();
+        let mut visible: Vec<_> = self.nodes.iter()
+            .filter(|(_,node)|shown(node))
+            .map(|(key,node)|(*key,Some(node)))
+            .chain(missing.into_iter().map(|key|(key,None)))
+            .collect();
+        visible.sort_by_key(|(key,_)|*key);
+        visible
+    }
+
+    fn label(&self, key:&Key, node:Option<&Node>)->Vec<String> {
+        let mut lines = Vec::new();
+        let exit = node.is_some_and(|node|matches!(node.kind, Some(Kind::Exit)));
+        lines.push(match key {
+            Key::Task(id) => format!("task#{id}{}", if exit {" (exit)"} else {""}),
+            Key::Anonymous(_) => format!("task{}", if exit {" (exit)"} else {""}),
+        });
+        let Some(node) = node else {
+            lines.push("missing".to_string());
+            return lines;
+        };
+        if let Some(qid) = node.qid {
+            lines.push(format!("Q#{qid}"));
+        }
+        for (i,(typename,filled)) in node.inputs.iter().enumerate() {
+            let check = if self.state && *filled == Some(true) {" ✓"} else {""};
+            lines.push(format!("in{i}: {typename}{check}"));
+        }
+        if self.state {
+            lines.push(match node.state {
+                State::Waiting => {
+                    let filled = node.inputs.iter().filter(|(_,filled)|*filled == Some(true)).count();
+                    format!("waiting {filled}/{}", node.inputs.len())
+                }
+                State::Queued => "queued".to_string(),
+                State::Done => "done".to_string(),
+            });
+        }
+        lines
+    }
+
+    fn edge_label(edge:&Edge)->String {
+        format!("{}{}", if edge.input {"in"} else {"out"}, edge.argidx)
+    }
+
+    /// renders the graph in the Graphviz DOT language.
+    pub fn to_dot(&self)->String {
+        let escape = |s:&str|s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph tasks {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (key,node) in self.visible() {
+            let label: Vec<_> = self.label(&key, node).iter().map(|line|escape(line)).collect();
+            let style = match node.map(|node|node.state) {
+                None => ", style=dashed, color=red",
+                Some(_) if !self.state => "",
+                Some(State::Waiting) => ", style=filled, fillcolor=lightyellow",
+                Some(State::Queued) => ", style=filled, fillcolor=lightblue",
+                Some(State::Done) => ", style=filled, fillcolor=lightgrey",
+            };
+            let _ = writeln!(dot, "    {} [label=\"{}\"{style}];", key.name(), label.join("\\n"));
+        }
+        for edge in &self.edges {
+            let _ = writeln!(dot, "    {} -> {} [label=\"{}\"];", edge.from.name(), edge.to.name(), Self::edge_label(edge));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// renders the graph as a Mermaid flowchart.
+    pub fn to_mermaid(&self)->String {
+        let escape = |s:&str|s.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+        let mut mermaid = String::from("flowchart LR\n");
+        let mut classes: BTreeMap<&str,Vec<String>> = BTreeMap::new();
+        for (key,node) in self.visible() {
+            let label: Vec<_> = self.label(&key, node).iter().map(|line|escape(line)).collect();
+            let _ = writeln!(mermaid, "    {}[\"{}\"]", key.name(), label.join("<br/>"));
+            let class = match node.map(|node|node.state) {
+                None => Some("missing"),
+                Some(_) if !self.state => None,
+                Some(State::Waiting) => Some("waiting"),
+                Some(State::Queued) => Some("queued"),
+                Some(State::Done) => Some("done"),
+            };
+            if let Some(class) = class {
+                classes.entry(class).or_default().push(key.name());
+            }
+        }
+        for edge in &self.edges {
+            let _ = writeln!(mermaid, "    {} -->|{}| {}", edge.from.name(), Self::edge_label(edge), edge.to.name());
+        }
+        for (class,names) in classes {
+            let style = match class {
+                "waiting" => "fill:#ffffe0",
+                "queued" => "fill:#add8e6",
+                "done" => "fill:#d3d3d3",
+                _ => "stroke:#f00,stroke-dasharray:5 5",
+            };
+            let _ = writeln!(mermaid, "    classDef {class} {style}");
+            let _ = writeln!(mermaid, "    class {} {class}", names.join(","));
+        }
+        mermaid
+    }
+}
+
+impl std::fmt::Debug for GraphExport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"GraphExport{{nodes:{},edges:{}}}",self.nodes.len(),self.edges.len())
+    }
+}
+
+#[test]
+fn test_export_graph() {
+    use crate::{Pool, Queue, TaskBuildNew};
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+
+    let sum = submitter.submit((|a:i32,b:i32|a+b).into_exit_task()).take();
+    let id = sum.taskid().0.unwrap();
+    submitter.submit((||1).into_task().bind_to(sum.input_ca::<0>()));
+    // bound to a task canceled
+    let other = submitter.submit((|a:u8|a).into_task()).take();
+    submitter.submit((||3u8).into_task().bind_to(other.input_ca::<0>()));
+    submitter.cancel(other.taskid());
+    let other = other.taskid().0.unwrap();
+    // bound to none, not shown
+    submitter.submit((||()).into_task());
+
+    let graph = pool.export_graph();
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph tasks {\n"));
+    assert!(dot.contains(&format!("    t{id} [label=\"task#{id} (exit)\\nQ#{qid}\\nin0: i32\\nin1: i32\"];\n")));
+    assert!(dot.contains(&format!("    a1 -> t{id} [label=\"in0\"];\n")));
+    assert!(dot.contains(&format!("    a2 [label=\"task\\nQ#{qid}\"];\n")));
+    assert!(dot.contains(&format!("    t{other} [label=\"task#{other}\\nmissing\", style=dashed, color=red];\n")));
+    assert!(dot.contains(&format!("    a2 -> t{other} [label=\"in0\"];\n")));
+    assert!(!dot.contains("waiting"));
+    assert!(!dot.contains("a3"));
+
+    let graph = graph.with_state(true);
+    let dot = graph.to_dot();
+    assert!(dot.contains("in1: i32\\nwaiting 0/2\", style=filled, fillcolor=lightyellow];\n"));
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.starts_with("flowchart LR\n"));
+    assert!(mermaid.contains(&format!("    t{id}[\"task#{id} (exit)<br/>Q#{qid}<br/>in0: i32<br/>in1: i32<br/>waiting 0/2\"]\n")));
+    assert!(mermaid.contains(&format!("    a1 -->|in0| t{id}\n")));
+    assert!(mermaid.contains(&format!("    class t{id} waiting\n")));
+    assert!(mermaid.contains("    class a1,a2 queued\n"));
+}
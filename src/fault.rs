@@ -0,0 +1,173 @@
+//! # `fault` module
+//!
+//! Isolates panics raised by task bodies, so that a bad task only fails itself,
+//! and the worker thread keeps serving its queue.
+//!
+//! Each caught panic is described by a [`TaskPanic`] and delivered to the handler
+//! registered by [`Pool::on_panic()`](crate::Pool::on_panic).
+
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::{Cell, RefCell},
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Once, RwLock},
+};
+
+use crate::cond::TaskId;
+use crate::observe::Observers;
+
+/// Describes a panic raised while running a task.
+pub struct TaskPanic {
+    taskid: TaskId,
+    qid: usize,
+    thread: Option<String>,
+    message: String,
+    payload: Box<dyn Any + Send>,
+    backtrace: Option<Backtrace>,
+}
+
+impl TaskPanic {
+    /// the id of the task that panicked,
+    /// `TaskId::NONE` for tasks without conditions and without explicit id.
+    pub fn taskid(&self)->TaskId {
+        self.taskid
+    }
+    /// the ID of the queue served by the worker thread, 0 if the thread is not spawned by a `Pool`.
+    pub fn qid(&self)->usize {
+        self.qid
+    }
+    /// the name of the worker thread, e.g. `taskorch-q1-w0` for the threads spawned by a `Pool`.
+    pub fn thread_name(&self)->Option<&str> {
+        self.thread.as_deref()
+    }
+    /// the panic message if the payload is a `&str` or `String`.
+    pub fn message(&self)->&str {
+        &self.message
+    }
+    /// the raw payload passed to `panic!()`.
+    pub fn payload(&self)->&(dyn Any + Send) {
+        &*self.payload
+    }
+    /// takes the raw payload, e.g. to resume the panic by `std::panic::resume_unwind()`.
+    pub fn into_payload(self)->Box<dyn Any + Send> {
+        self.payload
+    }
+    /// the backtrace captured at the panic point,
+    /// only available when enabled by `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+    pub fn backtrace(&self)->Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+}
+
+impl Debug for TaskPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"TaskPanic{{{:?},Q#{},{:?}}}",self.taskid,self.qid,self.message)
+    }
+}
+
+pub(crate) type Handler<E> = dyn Fn(E) + Send + Sync;
+
+/// The pool-wide slot of a handler, shared with all the threads reporting `E`.
+pub(crate) struct Report<E>(Arc<RwLock<Option<Box<Handler<E>>>>>);
+
+impl<E> Report<E> {
+    pub(crate) fn set(&self, handler:Box<Handler<E>>) {
+        *self.0.write().unwrap() = Some(handler);
+    }
+    pub(crate) fn report(&self, e:E) {
+        let lock = self.0.read().unwrap();
+        if let Some(handler) = lock.as_ref() {
+            handler(e);
+        }
+    }
+}
+
+impl<E> Clone for Report<E> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<E> Default for Report<E> {
+    fn default() -> Self {
+        Self(Arc::new(RwLock::new(None)))
+    }
+}
+
+pub(crate) type PanicReport = Report<TaskPanic>;
+
+thread_local! {
+    static IN_TASK: Cell<bool> = const { Cell::new(false) };
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Chains a panic hook once, which captures the backtrace of panics raised inside tasks.
+/// The previous hook is always called, so the panic message is still printed as usual.
+fn install_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(||{
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info|{
+            if IN_TASK.with(|t|t.get()) {
+                let bt = Backtrace::capture();
+                if let BacktraceStatus::Captured = bt.status() {
+                    BACKTRACE.with(|b|*b.borrow_mut() = Some(bt));
+                }
+            }
+            prev(info);
+        }));
+    });
+}
+
+pub(crate) fn panic_message(payload:&(dyn Any + Send))->String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "(unknown)".to_string()
+    }
+}
+
+/// drops the backtrace of a panic caught without being reported, e.g. by a retried task.
+pub(crate) fn discard_backtrace() {
+    BACKTRACE.with(|b|b.borrow_mut().take());
+}
+
+/// Runs the task and its postdo, catching any panic and reporting it.
+/// Returns `false` if a panic has been caught.
+pub(crate) fn run_isolated(f:impl FnOnce(), taskid:TaskId, qid:usize, report:&PanicReport, observers:&Observers)->bool {
+    install_hook();
+    IN_TASK.with(|t|t.set(true));
+    let r = panic::catch_unwind(AssertUnwindSafe(f));
+    IN_TASK.with(|t|t.set(false));
+    let Err(payload) = r else {
+        return true;
+    };
+    let message = panic_message(&*payload);
+    let backtrace = BACKTRACE.with(|b|b.borrow_mut().take());
+    let thread = std::thread::current().name().map(str::to_string);
+    error!("task#{taskid:?} panicked in Q#{qid}: {message}");
+    let panic = TaskPanic { taskid, qid, thread, message, payload, backtrace };
+    observers.notify(|o|o.panicked(&panic));
+    report.report(panic);
+    false
+}
+
+#[test]
+fn test_run_isolated() {
+    use std::sync::Mutex;
+    let report = PanicReport::default();
+    let caught = Arc::new(Mutex::new(Vec::new()));
+    let caught2 = caught.clone();
+    report.set(Box::new(move |p|caught2.lock().unwrap().push((p.taskid(),p.message().to_string()))));
+
+    let observers = Observers::default();
+    assert!(run_isolated(||{}, TaskId::new(1), 1, &report, &observers));
+    assert!(!run_isolated(||panic!("boom"), TaskId::new(2), 1, &report, &observers));
+    assert!(!run_isolated(||panic!("{}", 3), TaskId::new(3), 1, &report, &observers));
+    let caught = caught.lock().unwrap();
+    assert_eq!(caught.as_slice(), &[(TaskId::new(2),"boom".to_string()),(TaskId::new(3),"3".to_string())]);
+}
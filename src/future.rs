@@ -0,0 +1,145 @@
+//! # `future` module, with the `async` feature
+//!
+//! A minimal executor to drive a future to completion on the current thread,
+//! so that an `async` block can be the body of a task run by a worker thread,
+//! see [`TaskBuildAsync::into_async_task()`].
+//!
+//! The worker is blocked while the future is pending, it is parked until the future is woken up.
+//! No runtime is required, but a future relying on the reactor of a specific runtime
+//! (e.g. its sockets or timers) has to be run by that runtime instead.
+//!
+//! A task with conditions can drive an `async` block by [`block_on()`] in its closure:
+//! ```rust
+//! # use taskorch::{block_on, TaskBuildNew};
+//! let task = (|a:i32| block_on(async move { a + 1 })).into_task();
+//! ```
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::pin,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use crate::task::{OneToOne, PassthroughMapFn};
+use crate::curry::Currier;
+use crate::{TaskBuildNew, TaskNeed};
+
+/// wakes up the thread blocked on the future
+struct ThreadWaker {
+    thread: Thread,
+    /// set by a wake, so a wake before parking is not lost
+    woken: AtomicBool,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self:Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self:&Arc<Self>) {
+        if !self.woken.swap(true, Ordering::Release) {
+            self.thread.unpark();
+        }
+    }
+}
+
+/// Runs the future to completion on the current thread, and returns its output.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::block_on;
+/// assert_eq!(block_on(async { 6*7 }), 42);
+/// ```
+pub fn block_on<Fut:Future>(fut:Fut)->Fut::Output {
+    let mut fut = pin!(fut);
+    let thread_waker = Arc::new(ThreadWaker { thread: thread::current(), woken: AtomicBool::new(false) });
+    let waker = Waker::from(thread_waker.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(r) = fut.as_mut().poll(&mut cx) {
+            return r;
+        }
+        // the spurious wakeups of park are filtered out
+        while !thread_waker.woken.swap(false, Ordering::Acquire) {
+            thread::park();
+        }
+    }
+}
+
+/// the task driving a future, `F` calls `block_on()`
+type AsyncTask<F,R> = TaskNeed<Currier<F,(),R>,PassthroughMapFn<R>,(R,),OneToOne<(R,)>>;
+
+/// Constructs a task from an `async` block, driven by [`block_on()`] on the worker thread.
+pub trait TaskBuildAsync<R> {
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildAsync};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// pool.spawn_thread_for(qid);
+    ///
+    /// let (_, handle) = submitter.submit_with_handle(async { 6*7 }.into_async_task());
+    /// assert_eq!(handle.wait(), Ok(42));
+    /// pool.shutdown(taskorch::Shutdown::Drain);
+    /// ```
+    fn into_async_task(self)
+        -> AsyncTask<impl FnOnce()->R + Send + 'static,R>;
+
+    /// same as `into_async_task()`, the worker running it exits after.
+    fn into_async_exit_task(self)
+        -> AsyncTask<impl FnOnce()->R + Send + 'static,R>;
+}
+
+impl<Fut> TaskBuildAsync<Fut::Output> for Fut
+    where
+    Fut: Future + Send + 'static,
+    Fut::Output: Debug,
+{
+    fn into_async_task(self)
+        -> AsyncTask<impl FnOnce()->Fut::Output + Send + 'static,Fut::Output>
+    {
+        (move||block_on(self)).into_task()
+    }
+
+    fn into_async_exit_task(self)
+        -> AsyncTask<impl FnOnce()->Fut::Output + Send + 'static,Fut::Output>
+    {
+        (move||block_on(self)).into_exit_task()
+    }
+}
+
+#[test]
+fn test_async_task() {
+    use crate::{Pool, Queue};
+    use std::{sync::mpsc, time::Duration};
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    pool.spawn_thread_for(qid);
+
+    // an async task awaiting a value sent by another thread
+    let (tx,rx) = mpsc::channel::<crate::ResultHandle<i32>>();
+    let (add,add_handle) = submitter.submit_with_handle((|a:i32|a+1).into_task());
+    let add = add.take();
+    let task = async move {
+        let handle = rx.recv().unwrap();
+        handle.await.unwrap() * 10
+    }.into_async_task().bind_to(add.input_ca::<0>());
+    submitter.submit(task);
+    let q2 = pool.insert_queue(&Queue::new()).unwrap();
+    pool.spawn_thread_for(q2);
+    let (_,handle) = pool.task_submitter(q2).unwrap().submit_with_handle((||{
+        std::thread::sleep(Duration::from_millis(20));
+        4
+    }).into_task());
+    tx.send(handle).unwrap();
+    assert_eq!(add_handle.wait(), Ok(41));
+
+    // awaited from outside the pool
+    let (_,handle) = submitter.submit_with_handle((||->i32{panic!("abandoned")}).into_task());
+    assert_eq!(block_on(handle), Err(crate::ResultError::Abandoned));
+    pool.shutdown(crate::Shutdown::Drain);
+}
@@ -0,0 +1,396 @@
+//! # `graph` module
+//!
+//! Checks the wiring of the task graph before it runs, see [`Pool::validate()`](crate::Pool::validate)
+//! and [`GraphBatch::validate()`](crate::GraphBatch::validate).
+//!
+//! The graph is made of the conditional tasks waiting in the waitQueue, the inputs of the graph,
+//! and of the bindings of the tasks not run yet, waiting or queued, its edges.
+//! A mis-wired graph is reported by a [`GraphReport`] instead of error logs at runtime,
+//! or of tasks silently never run.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    num::NonZeroUsize,
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex, MutexGuard},
+};
+
+use crate::cond::{CondAddr, Section, TaskId};
+use crate::meta::TupleCondAddr;
+use crate::queue::TaskDo;
+use crate::task::Kind;
+
+/// A problem found in the graph of tasks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphIssue {
+    /// the task bound to is not waiting, it has not been submitted or it has been run.
+    Dangling { from: TaskId, to: TaskId, argidx: u8 },
+    /// the task bound to has no input at `argidx`.
+    NoSuchInput { from: TaskId, to: TaskId, argidx: u8 },
+    /// the input bound to has another type.
+    TypeMismatch { from: TaskId, to: TaskId, argidx: u8, expected: &'static str, found: &'static str },
+    /// no task is bound to the input, which has not been received either, so the task never runs.
+    Unbound { task: TaskId, argidx: u8, typename: &'static str },
+    /// the tasks wait for each other, each one bound to the next, the last one to the first.
+    Cycle(Vec<TaskId>),
+}
+
+/// The problems found by a validation, none if the graph is well wired.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{Pool, Queue, TaskBuildNew, GraphIssue};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+///
+/// // nothing is bound to its 2nd input
+/// let sum = submitter.submit((|a:i32,b:i32|a+b).into_task()).take();
+/// submitter.submit((||1).into_task().bind_to(sum.input_ca::<0>()));
+///
+/// let report = pool.validate();
+/// assert!(!report.is_ok());
+/// assert_eq!(report.issues(), &[GraphIssue::Unbound { task: sum.taskid(), argidx: 1, typename: "i32" }]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GraphReport {
+    issues: Vec<GraphIssue>,
+}
+
+impl GraphReport {
+    /// whether no problem is found
+    pub fn is_ok(&self)->bool {
+        self.issues.is_empty()
+    }
+
+    pub fn issues(&self)->&[GraphIssue] {
+        &self.issues
+    }
+
+    pub fn into_issues(self)->Vec<GraphIssue> {
+        self.issues
+    }
+}
+
+/// Where a task delivers one of its results, see `TaskNeed::bind_to()`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Target {
+    pub(crate) to: NonZeroUsize,
+    pub(crate) input: bool,
+    pub(crate) argidx: u8,
+    pub(crate) typename: &'static str,
+}
+
+impl Target {
+    /// `None` if the address is not bound, or the value is `()` which is never delivered.
+    pub(crate) fn of<T>(ca:&CondAddr<T>)->Option<Self> {
+        Self::at(ca.taskid(), *ca.section() == Section::Input, ca.argidx().i(), std::any::type_name::<T>())
+    }
+
+    fn at(to:TaskId, input:bool, argidx:u8, typename:&'static str)->Option<Self> {
+        let TaskId(Some(to)) = to else {
+            return None;
+        };
+        if typename == "()" {
+            return None;
+        }
+        Some(Self { to, input, argidx, typename })
+    }
+
+    /// the targets of the addresses a task is bound to by `TaskNeed::bind_all_to()`.
+    pub(crate) fn all<R:TupleCondAddr>(tca:&R::TCA)->Vec<Self> {
+        let mut targets = Vec::new();
+        R::each_addr(tca, &mut |to,input,argidx,typename|targets.extend(Self::at(to, input, argidx, typename)));
+        targets
+    }
+}
+
+/// An input of a waiting task.
+#[derive(Debug, Clone)]
+pub(crate) struct Input {
+    pub(crate) typename: &'static str,
+    /// whether the condition has been received
+    pub(crate) set: bool,
+}
+
+/// the inputs of a task, none if it has no conditions.
+pub(crate) fn inputs(task:&mut TaskDo)->Vec<Input> {
+    let Some(param) = task.as_param_mut() else {
+        return Vec::new();
+    };
+    (0..param.arity())
+        .map(|i|Input { typename: param.typename(i), set: param.is_set(i) })
+        .collect()
+}
+
+/// the count of the shards of the registry, so the submitters rarely contend for one.
+const SHARDS: usize = 16;
+
+#[derive(Default)]
+struct Registry {
+    next: AtomicU64,
+    /// the tasks not run yet, by their serial
+    shards: [Mutex<HashMap<u64,Producer>>; SHARDS],
+}
+
+impl Registry {
+    fn shard(&self, serial:u64)->MutexGuard<'_,HashMap<u64,Producer>> {
+        // may be poisoned if a validation panicked, the map is still valid.
+        self.shards[serial as usize % SHARDS].lock().unwrap_or_else(|e|e.into_inner())
+    }
+
+    fn producers(&self)->impl Iterator<Item=MutexGuard<'_,HashMap<u64,Producer>>> {
+        (0..SHARDS as u64).map(|serial|self.shard(serial))
+    }
+}
+
+/// The tasks not run yet which are bound to others, with their bindings, shared by a pool and its submitters.
+#[derive(Clone, Default)]
+pub(crate) struct Producers(Arc<Registry>);
+
+impl Producers {
+    /// reserves the entry of a task, the guard is moved into its postdo.
+    pub(crate) fn reserve(&self)->Producing {
+        let serial = self.0.next.fetch_add(1, Ordering::Relaxed) + 1;
+        Producing { serial, producers: self.clone() }
+    }
+
+    /// records the task until it has been run or dropped, nothing if it is bound to none,
+    /// called before the task is put into a queue or the waitQueue.
+    pub(crate) fn register(&self, producer:Option<Producer>) {
+        let Some(producer) = producer else {
+            return;
+        };
+        self.0.shard(producer.serial).insert(producer.serial, producer);
+    }
+
+    /// the tasks not run yet
+    pub(crate) fn snapshot(&self)->Vec<Producer> {
+        self.0.producers().flat_map(|shard|shard.values().cloned().collect::<Vec<_>>()).collect()
+    }
+
+    fn bindings(&self)->Vec<(TaskId,Vec<Target>)> {
+        self.0.producers()
+            .flat_map(|shard|shard.values().map(|producer|(producer.from,producer.targets.clone())).collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+/// A compiled task with its targets, to be registered once it is submitted.
+#[derive(Clone)]
+pub(crate) struct Producer {
+    pub(crate) serial: u64,
+    pub(crate) from: TaskId,
+    pub(crate) kind: Kind,
+    /// the queue the task is put into, when released for a conditional one
+    pub(crate) qid: usize,
+    /// the types of its inputs
+    pub(crate) inputs: Vec<&'static str>,
+    pub(crate) targets: Vec<Target>,
+}
+
+impl Producer {
+    pub(crate) fn new(producing:&Producing, task:&mut TaskDo, qid:usize, targets:Vec<Target>)->Self {
+        let inputs = inputs(task).into_iter().map(|input|input.typename).collect();
+        Self { serial: producing.serial, from: task.id(), kind: task.kind(), qid, inputs, targets }
+    }
+}
+
+/// Removes the entry of a task once it has been run or dropped.
+pub(crate) struct Producing {
+    serial: u64,
+    producers: Producers,
+}
+
+impl Drop for Producing {
+    fn drop(&mut self) {
+        self.producers.0.shard(self.serial).remove(&self.serial);
+    }
+}
+
+/// The part of the graph added after `Graph::focus()`, the only one whose issues are reported.
+struct Focus {
+    nodes: HashSet<NonZeroUsize>,
+    /// the index of the first binding added
+    bindings: usize,
+}
+
+/// The graph to validate: the waiting tasks with their inputs, and the bindings of the tasks not run yet.
+#[derive(Default)]
+pub(crate) struct Graph {
+    nodes: HashMap<NonZeroUsize,Vec<Input>>,
+    bindings: Vec<(TaskId,Vec<Target>)>,
+    focus: Option<Focus>,
+}
+
+impl Graph {
+    pub(crate) fn new(producers:&Producers)->Self {
+        Self { nodes: HashMap::new(), bindings: producers.bindings(), focus: None }
+    }
+
+    /// only the issues of the tasks added from now on are reported, e.g. the tasks of a batch,
+    /// so the other ones waiting in the pool do not fail it.
+    pub(crate) fn focus(&mut self) {
+        self.focus = Some(Focus { nodes: HashSet::new(), bindings: self.bindings.len() });
+    }
+
+    pub(crate) fn add_node(&mut self, taskid:NonZeroUsize, inputs:Vec<Input>) {
+        if let Some(focus) = &mut self.focus {
+            focus.nodes.insert(taskid);
+        }
+        self.nodes.insert(taskid, inputs);
+    }
+
+    fn in_focus(&self, taskid:&NonZeroUsize)->bool {
+        self.focus.as_ref().is_none_or(|focus|focus.nodes.contains(taskid))
+    }
+
+    pub(crate) fn add_bindings(&mut self, from:TaskId, targets:Vec<Target>) {
+        if !targets.is_empty() {
+            self.bindings.push((from,targets));
+        }
+    }
+
+    pub(crate) fn validate(&self)->GraphReport {
+        let mut issues = Vec::new();
+        let mut bound = HashSet::new();
+        let mut edges: HashMap<NonZeroUsize,Vec<NonZeroUsize>> = HashMap::new();
+        let focused = self.focus.as_ref().map_or(0, |focus|focus.bindings);
+        for (index,(from,targets)) in self.bindings.iter().enumerate() {
+            // the issues of the bindings out of focus are ignored, they still bind inputs
+            let mut issues = (index >= focused).then_some(&mut issues);
+            for Target { to, input, argidx, typename } in targets {
+                let (from,argidx) = (*from,*argidx);
+                let issue = match self.nodes.get(to) {
+                    None => Some(GraphIssue::Dangling { from, to: TaskId(Some(*to)), argidx }),
+                    Some(inputs) => match inputs.get(argidx as usize).filter(|_|*input) {
+                        None => Some(GraphIssue::NoSuchInput { from, to: TaskId(Some(*to)), argidx }),
+                        Some(expected) if expected.typename != *typename => Some(GraphIssue::TypeMismatch {
+                            from, to: TaskId(Some(*to)), argidx, expected: expected.typename, found: typename,
+                        }),
+                        Some(_) => None,
+                    },
+                };
+                if let Some(issue) = issue {
+                    if let Some(issues) = &mut issues {
+                        issues.push(issue);
+                    }
+                    continue;
+                }
+                bound.insert((*to,argidx));
+                if let TaskId(Some(from)) = from
+                    && self.nodes.contains_key(&from) {
+                    edges.entry(from).or_default().push(*to);
+                }
+            }
+        }
+        let mut ids: Vec<_> = self.nodes.keys().copied().collect();
+        ids.sort();
+        for taskid in ids.iter().filter(|id|self.in_focus(id)) {
+            for (i,input) in self.nodes[taskid].iter().enumerate() {
+                if !input.set && !bound.contains(&(*taskid,i as u8)) {
+                    issues.push(GraphIssue::Unbound { task: TaskId(Some(*taskid)), argidx: i as u8, typename: input.typename });
+                }
+            }
+        }
+        let cycles = cycles(&ids, &edges)
+            .into_iter()
+            .filter(|cycle|cycle.iter().any(|TaskId(id)|id.is_some_and(|id|self.in_focus(&id))));
+        issues.extend(cycles.map(GraphIssue::Cycle));
+        GraphReport { issues }
+    }
+}
+
+/// the cycles of the graph, each one found once, by a depth-first search.
+fn cycles(ids:&[NonZeroUsize], edges:&HashMap<NonZeroUsize,Vec<NonZeroUsize>>)->Vec<Vec<TaskId>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark { Visiting, Done }
+    let mut marks: HashMap<NonZeroUsize,Mark> = HashMap::new();
+    let mut found = Vec::new();
+    for &root in ids {
+        if marks.contains_key(&root) {
+            continue;
+        }
+        // the path from the root, with the next edge to follow of each node
+        let mut path: Vec<(NonZeroUsize,usize)> = vec![(root,0)];
+        marks.insert(root, Mark::Visiting);
+        while let Some((node,next)) = path.last_mut() {
+            let node = *node;
+            let Some(&to) = edges.get(&node).and_then(|e|e.get(*next)) else {
+                marks.insert(node, Mark::Done);
+                path.pop();
+                continue;
+            };
+            *next += 1;
+            match marks.get(&to) {
+                None => {
+                    marks.insert(to, Mark::Visiting);
+                    path.push((to,0));
+                }
+                Some(Mark::Visiting) => {
+                    let start = path.iter().position(|(n,_)|*n == to).unwrap_or(0);
+                    found.push(path[start..].iter().map(|(n,_)|TaskId(Some(*n))).collect());
+                }
+                Some(Mark::Done) => {}
+            }
+        }
+    }
+    found
+}
+
+impl Debug for Producers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Producers{{live:{}}}",self.0.producers().map(|shard|shard.len()).sum::<usize>())
+    }
+}
+
+#[test]
+fn test_validate_graph() {
+    let id = |i|NonZeroUsize::new(i).unwrap();
+    let tid = |i|TaskId(NonZeroUsize::new(i));
+    let input = |typename,set|Input { typename, set };
+    let target = |to,argidx,typename|Target { to: id(to), input: true, argidx, typename };
+    let mut graph = Graph::default();
+    graph.add_node(id(1), vec![input("i32",false), input("i32",true)]);
+    graph.add_node(id(2), vec![input("i32",false)]);
+    graph.add_node(id(3), vec![input("u8",false), input("u8",false)]);
+    // 1 <-> 2
+    graph.add_bindings(tid(1), vec![target(2,0,"i32")]);
+    graph.add_bindings(tid(2), vec![target(1,0,"i32")]);
+    graph.add_bindings(TaskId::NONE, vec![target(3,0,"u8"), target(3,1,"i32"), target(3,2,"u8"), target(9,0,"u8")]);
+    let report = graph.validate();
+    assert_eq!(report.issues(), &[
+        GraphIssue::TypeMismatch { from: TaskId::NONE, to: tid(3), argidx: 1, expected: "u8", found: "i32" },
+        GraphIssue::NoSuchInput { from: TaskId::NONE, to: tid(3), argidx: 2 },
+        GraphIssue::Dangling { from: TaskId::NONE, to: tid(9), argidx: 0 },
+        GraphIssue::Unbound { task: tid(3), argidx: 1, typename: "u8" },
+        GraphIssue::Cycle(vec![tid(1), tid(2)]),
+    ]);
+    assert!(Graph::default().validate().is_ok());
+
+    // the issues out of focus are ignored
+    graph.focus();
+    graph.add_node(id(4), vec![input("i32",false), input("i32",false)]);
+    graph.add_bindings(tid(4), vec![target(4,0,"i32"), target(8,0,"i32")]);
+    assert_eq!(graph.validate().issues(), &[
+        GraphIssue::Dangling { from: tid(4), to: tid(8), argidx: 0 },
+        GraphIssue::Unbound { task: tid(4), argidx: 1, typename: "i32" },
+        GraphIssue::Cycle(vec![tid(4)]),
+    ]);
+}
+
+#[test]
+fn test_producers() {
+    let producers = Producers::default();
+    let target = Target { to: NonZeroUsize::new(1).unwrap(), input: true, argidx: 0, typename: "i32" };
+    let producings: Vec<_> = (0..SHARDS*2).map(|_|producers.reserve()).collect();
+    for producing in &producings {
+        let from = TaskId::new(100+producing.serial as usize);
+        producers.register(Some(Producer { serial: producing.serial, from, kind: Kind::Normal, qid: 0, inputs: Vec::new(), targets: vec![target.clone()] }));
+    }
+    assert_eq!(producers.snapshot().len(), SHARDS*2);
+    assert_eq!(producers.bindings().len(), SHARDS*2);
+    // unregistered once run or dropped
+    drop(producings);
+    assert!(producers.snapshot().is_empty());
+}
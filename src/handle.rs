@@ -0,0 +1,241 @@
+//! # `handle` module
+//!
+//! A [`ResultHandle`] lets the submitting side get the return value of a task
+//! once a worker thread has run it.
+//!
+//! The handle is created by [`TaskSubmitter::submit_with_handle()`](crate::TaskSubmitter::submit_with_handle),
+//! the value is cloned into the handle right before it is passed to the bound downstream tasks,
+//! so binding and waiting can be used together.
+//!
+//! If the task is dropped without producing a value (e.g. it is replaced by
+//! another task with the same id, or it panicked), the handle reports [`ResultError::Abandoned`]
+//! instead of blocking forever.
+//!
+//! With the `async` feature, the handle is also a [`Future`], so it can be awaited
+//! by any executor, no runtime is required by this crate.
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// Error returned when the result of a task can not be obtained from a [`ResultHandle`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ResultError {
+    /// The task has not produced its result yet.
+    /// returned by `try_get()` or when `wait_timeout()` times out.
+    Pending,
+    /// The task was dropped before producing its result, it will never come.
+    Abandoned,
+    /// The result has already been taken out by a previous call.
+    Taken,
+}
+
+enum Slot<R> {
+    Pending,
+    Ready(R),
+    Taken,
+    Abandoned,
+}
+
+impl<R> Slot<R> {
+    /// takes out the value if ready, and leave the slot `Taken`.
+    fn take(&mut self)->Result<R,ResultError> {
+        match self {
+            Slot::Pending => Err(ResultError::Pending),
+            Slot::Taken => Err(ResultError::Taken),
+            Slot::Abandoned => Err(ResultError::Abandoned),
+            Slot::Ready(_) => {
+                let Slot::Ready(r) = std::mem::replace(self, Slot::Taken) else {
+                    unreachable!("the slot has been checked to be ready");
+                };
+                Ok(r)
+            }
+        }
+    }
+}
+
+struct Shared<R> {
+    slot: Mutex<Slot<R>>,
+    cond: Condvar,
+    /// the task awaiting the handle, locked after the slot
+    #[cfg(feature = "async")]
+    waker: Mutex<Option<Waker>>,
+}
+
+impl<R> Shared<R> {
+    /// wakes up the waiting threads and the awaiting task, called with the slot locked.
+    fn notify(&self) {
+        self.cond.notify_all();
+        #[cfg(feature = "async")]
+        if let Some(waker) = self.waker.lock().unwrap_or_else(|e|e.into_inner()).take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A handle to the result of a submitted task.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{Pool, Queue, TaskBuildNew};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+///
+/// let (_, handle) = submitter.submit_with_handle((||6*7).into_task());
+/// submitter.submit((||{}).into_exit_task());
+/// pool.spawn_thread_for(qid);
+///
+/// assert_eq!(handle.wait(), Ok(42));
+/// pool.join();
+/// ```
+pub struct ResultHandle<R>(Arc<Shared<R>>);
+
+/// The sending half, moved into the `PostDo` of the task.
+/// If dropped before sending, the handle is notified with `Abandoned`.
+pub(crate) struct ResultSender<R>(Option<Arc<Shared<R>>>);
+
+pub(crate) fn result_channel<R>()->(ResultSender<R>,ResultHandle<R>) {
+    let shared = Arc::new(Shared {
+        slot: Mutex::new(Slot::Pending),
+        cond: Condvar::new(),
+        #[cfg(feature = "async")]
+        waker: Mutex::new(None),
+    });
+    (ResultSender(Some(shared.clone())), ResultHandle(shared))
+}
+
+impl<R> ResultSender<R> {
+    pub(crate) fn send(mut self, r:R) {
+        if let Some(shared) = self.0.take() {
+            let mut slot = shared.slot.lock().unwrap();
+            *slot = Slot::Ready(r);
+            shared.notify();
+        }
+    }
+}
+
+impl<R> Drop for ResultSender<R> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.0.take() {
+            // may be poisoned if the handle side panicked, the state is still valid.
+            let mut slot = shared.slot.lock().unwrap_or_else(|e|e.into_inner());
+            *slot = Slot::Abandoned;
+            shared.notify();
+        }
+    }
+}
+
+impl<R> ResultHandle<R> {
+    /// Blocks the current thread until the result is available.
+    ///
+    /// # Returns
+    /// * `Ok(R)` - the value returned by the task
+    /// * `Err(Abandoned)` - the task was dropped and will never produce a value
+    /// * `Err(Taken)` - the value has been taken by a previous call
+    pub fn wait(&self)->Result<R,ResultError> {
+        let mut slot = self.0.slot.lock().unwrap();
+        while let Slot::Pending = *slot {
+            slot = self.0.cond.wait(slot).unwrap();
+        }
+        slot.take()
+    }
+
+    /// Blocks the current thread until the result is available or the `timeout` elapses.
+    ///
+    /// # Returns
+    /// Same as [`wait()`](Self::wait), and `Err(Pending)` if timed out.
+    pub fn wait_timeout(&self, timeout:Duration)->Result<R,ResultError> {
+        let deadline = Instant::now() + timeout;
+        let mut slot = self.0.slot.lock().unwrap();
+        while let Slot::Pending = *slot {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            slot = self.0.cond.wait_timeout(slot, deadline-now).unwrap().0;
+        }
+        slot.take()
+    }
+
+    /// Gets the result without blocking.
+    ///
+    /// # Returns
+    /// Same as [`wait()`](Self::wait), and `Err(Pending)` if the result is not ready yet.
+    pub fn try_get(&self)->Result<R,ResultError> {
+        self.0.slot.lock().unwrap().take()
+    }
+
+    /// Returns `true` if the result is ready to be taken without blocking.
+    pub fn is_ready(&self)->bool {
+        matches!(*self.0.slot.lock().unwrap(), Slot::Ready(_))
+    }
+}
+
+/// Awaits the result, the same as [`wait()`](ResultHandle::wait) without blocking the thread.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{block_on, Pool, Queue, TaskBuildNew};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+/// pool.spawn_thread_for(qid);
+///
+/// let (_, handle) = submitter.submit_with_handle((||6*7).into_task());
+/// // any executor will do
+/// assert_eq!(block_on(async { handle.await }), Ok(42));
+/// pool.shutdown(taskorch::Shutdown::Drain);
+/// ```
+#[cfg(feature = "async")]
+impl<R> Future for ResultHandle<R> {
+    type Output = Result<R,ResultError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.0.slot.lock().unwrap();
+        if let Slot::Pending = *slot {
+            let mut waker = self.0.waker.lock().unwrap();
+            match &mut *waker {
+                Some(w) if w.will_wake(cx.waker()) => {}
+                w => *w = Some(cx.waker().clone()),
+            }
+            return Poll::Pending;
+        }
+        Poll::Ready(slot.take())
+    }
+}
+
+impl<R> Debug for ResultHandle<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match *self.0.slot.lock().unwrap() {
+            Slot::Pending => "Pending",
+            Slot::Ready(_) => "Ready",
+            Slot::Taken => "Taken",
+            Slot::Abandoned => "Abandoned",
+        };
+        write!(f,"ResultHandle<{}>{{{state}}}",std::any::type_name::<R>())
+    }
+}
+
+#[test]
+fn test_result_channel() {
+    let (sender,handle) = result_channel::<i32>();
+    assert_eq!(handle.try_get(), Err(ResultError::Pending));
+    assert_eq!(handle.wait_timeout(Duration::from_millis(1)), Err(ResultError::Pending));
+    sender.send(3);
+    assert!(handle.is_ready());
+    assert_eq!(handle.wait(), Ok(3));
+    assert_eq!(handle.try_get(), Err(ResultError::Taken));
+
+    let (sender,handle) = result_channel::<i32>();
+    let th = std::thread::spawn(move||drop(sender));
+    assert_eq!(handle.wait(), Err(ResultError::Abandoned));
+    th.join().unwrap();
+}
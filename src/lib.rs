@@ -15,27 +15,63 @@ mod curry;
 mod queue;
 pub mod task;
 mod submitter;
+mod handle;
+mod fault;
+mod timer;
+mod expire;
+mod periodic;
+mod mpmc;
+mod scaling;
+mod retry;
+mod scope;
+mod batch;
+mod graph;
+mod export;
+mod stats;
+mod observe;
+#[cfg(feature = "async")]
+mod future;
 
 pub use cond::{
     CondAddr,TaskId,ArgIdx,Section
 };
 
-use queue::C1map;
-pub use queue::{spawn_thread, Queue};
+use queue::{C1map, Spawned, WorkerCtx, WorkerState, spawn_worker};
+use scaling::Scaler;
+use fault::PanicReport;
+use timer::Timer;
+use stats::Workers;
+pub use queue::{spawn_thread, Queue, QueueConfig, Cancellation, WorkerConfig};
 
 #[allow(deprecated)] // for TaskBuildOp will be removed at next ver.
 pub use task::{
     Kind,
+    Priority,
     TaskNeed,
     TaskBuildNew,TaskBuildOp,
     taskid_next,
 };
 
 pub use submitter::{TaskSubmitter,Submission,SummitResult,TaskSubmitError};
+pub use handle::{ResultHandle,ResultError};
+pub use fault::TaskPanic;
+pub use expire::{TaskExpired,ExpireAction};
+pub use periodic::{Schedule,Cron,CronError,PeriodicHandle};
+pub use scaling::Scaling;
+pub use retry::{Retry,Backoff};
+pub use scope::{scope,Scope,ScopedSubmitter};
+pub use batch::{GraphBatch,BatchError};
+pub use graph::{GraphReport,GraphIssue};
+pub use export::GraphExport;
+pub use stats::{PoolStats,QueueStats,WorkerStats,Histogram};
+pub use observe::{Observer,Delivery,DeliveryError};
+pub use log::{LogLevel,LogFormat,LogSink,WriterSink,MemorySink,set_log_level,log_level,set_log_format,log_format,set_log_sink};
+#[cfg(feature = "async")]
+pub use future::{block_on,TaskBuildAsync};
 
 
 /// a handle to a thread spawned for queue
-pub struct Jhandle(JoinHandle<()>,Arc<AtomicBool>);
+pub struct Jhandle(JoinHandle<()>,Arc<WorkerState>,Queue);
 
 impl Jhandle {
     /// record the thread handle into pool
@@ -43,11 +79,17 @@ impl Jhandle {
         pool.insert_thread_handle(self)
     }
 
-    /// the thread exit once the current task complete
+    /// the thread exit once the current task complete,
+    /// or at once if it is waiting for tasks.
     pub fn exit_next(&mut self) {
-        let _ = self.1.compare_exchange(
-            false, true,
-            Ordering::Acquire,Ordering::Relaxed);
+        self.1.stop();
+        self.2.wake_all();
+    }
+
+    /// the thread exit once no task is found in its queue.
+    fn exit_drained(&mut self) {
+        self.1.drain();
+        self.2.wake_all();
     }
 
     /// block until the thread has exited
@@ -56,11 +98,40 @@ impl Jhandle {
     }
 }
 
+/// How [`Pool::shutdown()`] stops the threads.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shutdown {
+    /// Each thread exits once no task is found in its queue,
+    /// the conditional tasks released into it meanwhile are run as well.
+    /// Draining is per queue: once the threads have exited, the tasks left in the queues are dropped,
+    /// e.g. released by a task of another queue after the threads of theirs exited, or in a queue without threads,
+    /// as are the conditional tasks still waiting for their conditions and the tasks held by the timer,
+    /// delayed or waiting to be retried; their result handles report `Abandoned`.
+    Drain,
+    /// Each thread exits once its current task completes, or at once if it is idle.
+    /// The tasks left in the queues and the waitQueue are kept.
+    AfterCurrent,
+    /// Same as `AfterCurrent`, and then the tasks left in the queues and the waitQueue are dropped,
+    /// their result handles report `Abandoned`.
+    Abort,
+}
+
 /// Pool, a container that holds and managers all resources, such as threads and queues
+///
+/// When dropped without being joined, it is shut down by [`Shutdown::AfterCurrent`].
 pub struct Pool {
     queues: HashMap<usize,Queue>,
     jhands: HashMap<usize,Jhandle>,
     c1map: C1map,
+    panics: PanicReport,
+    timer: Timer,
+    /// set when the pool is shut down, shared with the submitters
+    closed: Arc<AtomicBool>,
+    /// the count of the threads spawned for each queue, to name them
+    spawned: Spawned,
+    scalers: Vec<Arc<Scaler>>,
+    /// the live workers, for their metrics
+    workers: Workers,
     id_next: usize,
 }
 
@@ -72,6 +143,12 @@ impl Pool {
             queues: HashMap::new(),
             jhands: HashMap::new(),
             c1map: C1map::new(),
+            panics: PanicReport::default(),
+            timer: Timer::new(),
+            closed: Arc::new(AtomicBool::new(false)),
+            spawned: Spawned::default(),
+            scalers: Vec::new(),
+            workers: Workers::default(),
             id_next: 0,
         }
     }
@@ -87,7 +164,10 @@ impl Pool {
     pub fn task_submitter(&self, qid:usize)->Option<TaskSubmitter> {
         let queue = self.queues.get(&qid)?.clone();
         let c1map = self.c1map.clone();
-        TaskSubmitter {qid, queue, c1map}.into()
+        let timer = self.timer.clone();
+        let closed = self.closed.clone();
+        let panics = self.panics.clone();
+        TaskSubmitter {qid, queue, c1map, timer, closed, panics}.into()
     }
 
     /// gets the ref to Queue by ID
@@ -110,18 +190,196 @@ impl Pool {
     }
 
     /// return thread.id in pool
+    ///
+    /// The thread is named `taskorch-q{qid}-w{n}`, see [`WorkerConfig`].
     pub fn spawn_thread_for(&mut self, qid:usize)->Option<usize> {
-        let Some(queue) = self.queue(qid) else {
+        self.spawn_threads_for(qid, WorkerConfig::new())?.pop()
+    }
+
+    /// Spawns the threads for the queue as configured, returns their IDs in pool.
+    ///
+    /// Returns `None` if the queue does not exist.
+    /// If the OS fails to spawn a thread, the error is logged and the threads spawned so far are returned.
+    pub fn spawn_threads_for(&mut self, qid:usize, config:WorkerConfig)->Option<Vec<usize>> {
+        let Some(queue) = self.queue(qid).cloned() else {
             error!("Q#{qid} does not exist; thread starting is not allowed.");
             return None;
         };
-        spawn_thread(queue).collect_into(self)
+        let mut tids = Vec::with_capacity(config.get_count());
+        for _ in 0..config.get_count() {
+            let n = self.spawned.next(qid);
+            let mut ctx = WorkerCtx { qid, panics: self.panics.clone(), workers: self.workers.clone(), observers: self.c1map.observers().clone(), ..Default::default() };
+            let builder = config.builder(qid, n, &mut ctx);
+            match spawn_worker(&queue, ctx, builder) {
+                Ok(jhandle) => {
+                    tids.extend(jhandle.collect_into(self));
+                }
+                Err(_e) => {
+                    error!("failed to spawn a thread for Q#{qid}: {_e}");
+                    break;
+                }
+            }
+        }
+        Some(tids)
+    }
+
+    /// Serves the queue by elastic threads: `min` threads are spawned at once,
+    /// more are spawned up to `max` while tasks keep waiting in the queue,
+    /// and the ones beyond `min` retire after being idle for the keep-alive period, see [`Scaling`].
+    ///
+    /// Returns `None` if the queue does not exist or is already scaled.
+    ///
+    /// The load is checked by the timer thread of the pool.
+    /// On [`join()`](Pool::join), the scaled threads exit once the queue is drained, no exit task is needed.
+    /// An exit task popped by a scaled thread makes it exit as for other threads.
+    pub fn scale_for(&mut self, qid:usize, scaling:Scaling)->Option<()> {
+        let Some(queue) = self.queue(qid).cloned() else {
+            error!("Q#{qid} does not exist; scaling is not allowed.");
+            return None;
+        };
+        if self.scalers.iter().any(|s|s.qid() == qid) {
+            error!("Q#{qid} is already scaled.");
+            return None;
+        }
+        let ctx = WorkerCtx { qid, panics: self.panics.clone(), workers: self.workers.clone(), observers: self.c1map.observers().clone(), ..Default::default() };
+        let scaler = Scaler::new(qid, queue, scaling, ctx, self.spawned.clone());
+        scaler.start(&self.timer);
+        debug!("Q#{qid} is scaled by {scaler:?}.");
+        self.scalers.push(scaler);
+        Some(())
+    }
+
+    /// Registers the handler called when a task panics in any thread of this pool.
+    ///
+    /// The panic is caught, so the thread keeps serving its queue;
+    /// the result handle and bound tasks of the panicked task never receive its result.
+    /// Only one handler is kept, a later call replaces the former one.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildNew};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// pool.on_panic(move |p| tx.send(p).unwrap());
+    ///
+    /// submitter.submit((||panic!("bad task")).into_task());
+    /// submitter.submit((||println!("still alive")).into_exit_task());
+    /// pool.spawn_thread_for(qid);
+    /// pool.join();
+    ///
+    /// let p = rx.recv().unwrap();
+    /// assert_eq!(p.message(), "bad task");
+    /// assert_eq!(p.qid(), qid);
+    /// ```
+    pub fn on_panic<F>(&mut self, handler:F)
+        where F: Fn(TaskPanic) + Send + Sync + 'static
+    {
+        self.panics.set(Box::new(handler));
+    }
+
+    /// Registers the handler called when a conditional task expired,
+    /// see [`TaskNeed::expire_after()`].
+    ///
+    /// The handler is called in the timer thread of the pool, after the expire policy has been applied.
+    /// Only one handler is kept, a later call replaces the former one.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildNew, ExpireAction};
+    /// # use std::time::Duration;
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// pool.on_expire(move |e| tx.send(e).unwrap());
+    ///
+    /// // `b` never arrives, and is filled with 0 after 10ms
+    /// let add = (|a:i32,b:i32|println!("{a}+{b}={}",a+b))
+    ///     .into_exit_task()
+    ///     .expire_after_or_default(Duration::from_millis(10));
+    /// let add = submitter.submit(add).take();
+    /// submitter.submit((||1).into_task().bind_to(add.input_ca::<0>()));
+    /// pool.spawn_thread_for(qid);
+    ///
+    /// let e = rx.recv().unwrap();
+    /// assert_eq!(e.missing(), &[1]);
+    /// assert_eq!(e.action(), ExpireAction::Defaulted);
+    /// pool.join();
+    /// ```
+    pub fn on_expire<F>(&mut self, handler:F)
+        where F: Fn(TaskExpired) + Send + Sync + 'static
+    {
+        self.c1map.on_expire(Box::new(handler));
+    }
+
+    /// Checks the task graph of the pool: the bindings of the tasks not run yet,
+    /// and the inputs of the tasks waiting for them.
+    ///
+    /// The report is a snapshot, exact when no task is running meanwhile.
+    /// The next runs of the periodic tasks are not counted as producers.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildNew, GraphIssue};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    ///
+    /// // `b` has no producer
+    /// let add = submitter.submit((|a:i32,b:i32|a+b).into_task()).take();
+    /// submitter.submit((||1).into_task().bind_to(add.input_ca::<0>()));
+    /// let report = pool.validate();
+    /// assert!(matches!(report.issues(), [GraphIssue::Unbound { argidx: 1, .. }]));
+    /// ```
+    pub fn validate(&self)->GraphReport {
+        self.c1map.graph().validate()
+    }
+
+    /// A snapshot of the task graph of the pool, to be rendered as Graphviz DOT or Mermaid text,
+    /// see [`GraphExport`].
+    pub fn export_graph(&self)->GraphExport {
+        self.c1map.export()
+    }
+
+    /// A snapshot of the metrics of the pool: the counters and latencies of each queue,
+    /// the busy and idle time of each live thread, and the waitQueue, see [`PoolStats`].
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildNew};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// submitter.submit((||{}).into_exit_task());
+    ///
+    /// let stats = pool.stats();
+    /// let queue = stats.queue(qid).unwrap();
+    /// assert_eq!((queue.submitted(), queue.executed(), queue.len()), (1, 0, 1));
+    /// assert_eq!(stats.waiting(), 0);
+    /// ```
+    pub fn stats(&self)->PoolStats {
+        let queues = self.queues.iter().map(|(qid,queue)|queue.meter().snapshot(*qid, queue.len())).collect();
+        let (waiting,condition_wait) = self.c1map.stats();
+        PoolStats::new(queues, &self.workers, waiting, condition_wait)
+    }
+
+    /// Adds an observer of the lifecycle of the tasks of this pool: submitted, delivered or failed to,
+    /// released, started, finished or panicked, see [`Observer`].
+    ///
+    /// Several observers may be added, each one is called in the order they were added.
+    /// The threads spawned before are observed as well.
+    pub fn add_observer<O>(&mut self, observer:O)
+        where O: Observer + 'static
+    {
+        self.c1map.observers().add(Arc::new(observer));
     }
 
     fn insert_thread_handle(&mut self, jhandle:Jhandle)->Option<usize> {
         let id = self.next_id();
-        self.jhands.insert(id, jhandle)
-            .map(|_|id)
+        self.jhands.insert(id, jhandle);
+        Some(id)
     }
 
     #[allow(dead_code)]
@@ -139,27 +397,269 @@ impl Pool {
     }
 
     /// block until all threads have exited
-    pub fn join(self) {
-        let thcount = self.jhands.len();
+    ///
+    /// # Panics
+    /// If a thread panicked, once all of them have exited.
+    /// A panic raised by a task is caught and does not count, see [`on_panic()`](Self::on_panic).
+    pub fn join(mut self) {
+        let panics = self.join_threads();
+        Self::raise(panics);
+    }
+
+    /// Stops all the threads of the pool, and blocks until they have exited.
+    ///
+    /// The idle threads are woken up to exit, no exit task is needed.
+    /// The submitters are closed at once, the tasks submitted later are rejected,
+    /// see [`TaskSubmitError::PoolClosed`] and [`Submission::Rejected`].
+    /// The delayed and recurring tasks not due yet are dropped.
+    /// Panics as [`join()`](Self::join) if a thread panicked.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use taskorch::{Pool, Queue, TaskBuildNew, Shutdown, TaskSubmitError, ResultError};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// pool.spawn_thread_for(qid);
+    /// pool.spawn_thread_for(qid);
+    /// let (_, queued) = submitter.submit_with_handle((||1).into_task());
+    /// let (_, waiting) = submitter.submit_with_handle((|a:i32|a).into_task());
+    ///
+    /// // no exit task is needed
+    /// pool.shutdown(Shutdown::Drain);
+    /// assert_eq!(queued.wait(), Ok(1));
+    /// // the conditional task never got its condition
+    /// assert_eq!(waiting.wait(), Err(ResultError::Abandoned));
+    /// assert_eq!(submitter.try_submit((||2).into_task()).unwrap_err(), TaskSubmitError::PoolClosed);
+    /// ```
+    pub fn shutdown(mut self, mode:Shutdown) {
+        let panics = self.stop_threads(mode);
+        Self::raise(panics);
+    }
+
+    /// stops the autoscalers, and takes over their threads to be joined.
+    fn stop_scalers(&mut self)->Vec<usize> {
+        let mut tids = Vec::new();
+        for scaler in std::mem::take(&mut self.scalers) {
+            for jhandle in scaler.stop() {
+                tids.extend(jhandle.collect_into(self));
+            }
+        }
+        tids
+    }
+
+    /// returns the panics of the threads, see `join_threads()`.
+    fn stop_threads(&mut self, mode:Shutdown)->Vec<String> {
+        info!("pool is shutting down: {mode:?}.");
+        self.closed.store(true, Ordering::Release);
+        self.stop_scalers();
+        for jhand in self.jhands.values_mut() {
+            match mode {
+                Shutdown::Drain => jhand.exit_drained(),
+                Shutdown::AfterCurrent | Shutdown::Abort => jhand.exit_next(),
+            }
+        }
+        // the submitters blocked on full queues
+        for queue in self.queues.values() {
+            queue.wake_all();
+        }
+        let panics = self.join_threads();
+        match mode {
+            Shutdown::Drain => {
+                // released after the threads of their queues exited, or not served by any thread
+                for queue in self.queues.values() {
+                    queue.clear();
+                }
+                let _dropped = self.c1map.clear();
+                debug!("{_dropped} waiting cond-tasks dropped.");
+            }
+            Shutdown::AfterCurrent => {}
+            Shutdown::Abort => {
+                for queue in self.queues.values() {
+                    queue.clear();
+                }
+                let _dropped = self.c1map.clear();
+                debug!("{_dropped} waiting cond-tasks aborted.");
+            }
+        }
+        panics
+    }
+
+    /// joins all the threads, returns the messages of those which panicked, logged.
+    /// not panicking itself, for it is also called when the pool is dropped, maybe while unwinding.
+    fn join_threads(&mut self)->Vec<String> {
+        for tid in self.stop_scalers() {
+            if let Some(jhand) = self.jhands.get_mut(&tid) {
+                jhand.exit_drained();
+            }
+        }
+        let jhands = std::mem::take(&mut self.jhands);
+        let thcount = jhands.len();
         let mut threadid_list_log = String::with_capacity(thcount*"thread(123) ".len());
-        for (_innerid,handle) in self.jhands {
+        let mut panics = Vec::new();
+        for (_innerid,handle) in jhands {
             let thid = handle.0.thread().id();
             if let Err(err) = handle.0.join() {
-                let err = if let Some(s) = err.downcast_ref::<&str>() {
-                    format!("thread panic: {}", s)
-                } else if let Some(s) = err.downcast_ref::<String>() {
-                    format!("thead panic: {}", s)
-                } else {
-                    format!("thread panic (unknow)")
-                };
-                error!("{err}");
-                panic!("{}", err);
+                let message = crate::fault::panic_message(&*err);
+                error!("{thid:?} panicked: {message}");
+                panics.push(message);
+                continue;
             }
             let thidstr = format!("{:?} ",thid);
             threadid_list_log.push_str(&thidstr);
             info!("pool received normal exit from {thid:?}.");
         }
+        self.timer.close();
         info!("pool with {thcount} threads: [{threadid_list_log}] exited ok.");
+        panics
+    }
+
+    /// panics with the first panic of the threads joined, if any.
+    fn raise(panics:Vec<String>) {
+        if let Some(message) = panics.into_iter().next() {
+            panic!("thread panic: {message}");
+        }
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        if self.jhands.is_empty() && self.scalers.is_empty() {
+            self.timer.close();
+            return;
+        }
+        warn!("pool is dropped without being joined.");
+        // the panics of the threads are only logged, the pool may be dropped while unwinding
+        let _panics = self.stop_threads(Shutdown::AfterCurrent);
     }
 }
 
+#[test]
+fn test_shutdown() {
+    use std::{sync::mpsc, time::Duration};
+    // idle threads are woken up to exit
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    pool.spawn_thread_for(qid);
+    pool.spawn_thread_for(qid);
+    pool.exit_next_all();
+    pool.join();
+
+    // the queued tasks are dropped after the current one
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    let (tx,rx) = mpsc::channel();
+    submitter.submit((move||{
+        tx.send(()).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }).into_task());
+    let (_,queued) = submitter.submit_with_handle((||1).into_task());
+    pool.spawn_thread_for(qid);
+    rx.recv().unwrap();
+    pool.shutdown(Shutdown::Abort);
+    assert_eq!(queued.wait(), Err(ResultError::Abandoned));
+    assert!(submitter.is_closed());
+    assert!(matches!(submitter.submit((||2).into_task()), Submission::Rejected(_)));
+
+    // draining is per queue, a task released after the threads of its queue exited is dropped
+    let mut pool = Pool::new();
+    let first = pool.insert_queue(&Queue::new()).unwrap();
+    let second = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(first).unwrap();
+    let downstream = pool.task_submitter(second).unwrap();
+    let (task,released) = submitter.submit_with_handle((|a:i32|a).into_task().release_to(&downstream));
+    let (tx,rx) = mpsc::channel();
+    submitter.submit((move||{
+        tx.send(()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        1
+    }).into_task().bind_to(task.take().input_ca::<0>()));
+    pool.spawn_thread_for(first);
+    pool.spawn_thread_for(second);
+    rx.recv().unwrap();
+    pool.shutdown(Shutdown::Drain);
+    assert_eq!(released.wait_timeout(Duration::from_millis(300)), Err(ResultError::Abandoned));
+
+    // dropped without being joined
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    pool.spawn_thread_for(qid);
+    drop(pool);
+}
+
+#[test]
+fn test_thread_id() {
+    // the IDs of the threads recorded, not `None`
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let tid = pool.spawn_thread_for(qid).unwrap();
+    let collected = spawn_thread(pool.queue(qid).unwrap()).collect_into(&mut pool).unwrap();
+    assert_ne!(tid, collected);
+    assert!(pool.jhandle(tid).is_some() && pool.jhandle(collected).is_some());
+    assert_eq!(pool.spawn_thread_for(qid+100), None);
+    pool.shutdown(Shutdown::AfterCurrent);
+}
+
+#[test]
+fn test_join_panic() {
+    use std::panic::{self, AssertUnwindSafe};
+    // a thread panicking out of a task is raised by join()
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    pool.spawn_threads_for(qid, WorkerConfig::new().on_stop(||panic!("on_stop"))).unwrap();
+    pool.spawn_thread_for(qid);
+    let r = panic::catch_unwind(AssertUnwindSafe(||pool.shutdown(Shutdown::AfterCurrent)));
+    let payload = r.unwrap_err();
+    assert_eq!(crate::fault::panic_message(&*payload), "thread panic: on_stop");
+
+    // only logged when dropped
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    pool.spawn_threads_for(qid, WorkerConfig::new().on_stop(||panic!("on_stop"))).unwrap();
+    drop(pool);
+}
+
+#[test]
+fn test_worker_config() {
+    use std::sync::{atomic::AtomicUsize, mpsc};
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    let (tx,rx) = mpsc::channel();
+    pool.on_panic(move|p|tx.send(p.thread_name().map(str::to_string)).unwrap());
+    let started = Arc::new(AtomicUsize::new(0));
+    let stopped = Arc::new(AtomicUsize::new(0));
+    let (s1,s2) = (started.clone(),stopped.clone());
+    let config = WorkerConfig::new()
+        .name("io")
+        .stack_size(256 * 1024)
+        .count(2)
+        .on_start(move||{ s1.fetch_add(1, Ordering::Relaxed); })
+        .on_stop(move||{ s2.fetch_add(1, Ordering::Relaxed); });
+    assert_eq!(pool.spawn_threads_for(qid, config).unwrap().len(), 2);
+    assert_eq!(pool.spawn_threads_for(qid+100, WorkerConfig::new()), None);
+
+    submitter.submit((||panic!("named")).into_task());
+    let name = rx.recv().unwrap().unwrap();
+    assert!(name == "io-w0" || name == "io-w1", "{name}");
+
+    submitter.submit((||{}).into_exit_task());
+    submitter.submit((||{}).into_exit_task());
+
+    // the default name tells the queue, and counts the threads spawned for it
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    let tid = pool.spawn_thread_for(qid).unwrap();
+    assert!(pool.jhandle(tid).is_some());
+    pool.spawn_thread_for(qid);
+    // each thread exits after running one of them
+    submitter.submit((||panic!("default")).into_exit_task());
+    submitter.submit((||panic!("default")).into_exit_task());
+    let mut names: Vec<_> = (0..2).map(|_|rx.recv().unwrap().unwrap()).collect();
+    names.sort();
+    assert_eq!(names, vec![format!("taskorch-q{qid}-w0"), format!("taskorch-q{qid}-w1")]);
+    pool.join();
+    assert_eq!(started.load(Ordering::Relaxed), 2);
+    assert_eq!(stopped.load(Ordering::Relaxed), 2);
+}
@@ -2,13 +2,19 @@
 
 #[allow(unused_imports)]
 use std::fmt::Display;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
 use std::str::{from_utf8, from_utf8_unchecked};
-use std::sync::{Mutex, OnceLock};
-use std::time::Instant;
+use std::cell::Cell;
+use std::sync::{atomic::{AtomicU8, Ordering}, Arc, Mutex, OnceLock, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-#[allow(dead_code)]
-#[derive(PartialEq, PartialOrd)]
-pub(crate) enum Level {
+/// The level of the logs, from `Off` to the most verbose `Trace`, see [`set_log_level()`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
     Off,
     Error,
     Warn,
@@ -17,27 +23,72 @@ pub(crate) enum Level {
     Trace,
 }
 
+impl LogLevel {
+    fn from_u8(level:u8)->Self {
+        match level {
+            0 => LogLevel::Off,
+            1 => LogLevel::Error,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Info,
+            4 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+
+    fn name(&self)->&'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    /// the name of a level, case insensitive, e.g. `debug`.
+    fn parse(name:&str)->Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "off" => Some(LogLevel::Off),
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
 /// Log level options (mutually exclusive - select one or none)
 /// When multiple are selected, compilation will fail.
-pub(crate) const LEVEL: Level = {
+pub(crate) const LEVEL: LogLevel = {
     #[cfg(feature = "log-error")]
-    const MAX_LEVEL: Level = Level::Error;
+    const MAX_LEVEL: LogLevel = LogLevel::Error;
     #[cfg(feature = "log-warn")]
-    const MAX_LEVEL: Level = Level::Warn;
+    const MAX_LEVEL: LogLevel = LogLevel::Warn;
     #[cfg(feature = "log-info")]
-    const MAX_LEVEL: Level = Level::Info;
+    const MAX_LEVEL: LogLevel = LogLevel::Info;
     #[cfg(feature = "log-debug")]
-    const MAX_LEVEL: Level = Level::Debug;
+    const MAX_LEVEL: LogLevel = LogLevel::Debug;
     #[cfg(feature = "log-trace")]
-    const MAX_LEVEL: Level = Level::Trace;
+    const MAX_LEVEL: LogLevel = LogLevel::Trace;
+    // forwarded to a logger without a level selected: all, filtered by the logger
+    #[cfg(all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ))]
+    const MAX_LEVEL: LogLevel = LogLevel::Trace;
     #[cfg(not(any(
         feature = "log-error",
         feature = "log-warn",
         feature = "log-info",
         feature = "log-debug",
         feature = "log-trace",
+        feature = "log",
+        feature = "tracing",
     )))]
-    const MAX_LEVEL: Level = Level::Off;
+    const MAX_LEVEL: LogLevel = LogLevel::Off;
     MAX_LEVEL
 };
 
@@ -74,20 +125,379 @@ pub(crate) mod level_color {
 #[allow(unused_imports)]
 pub(crate) use level_color::*;
 
+/// the environment variable read for the level at the first log, unless set by [`set_log_level()`].
+pub(crate) const LEVEL_ENV: &str = "TASKORCH_LOG";
+const LEVEL_UNSET: u8 = u8::MAX;
+static RUNTIME_LEVEL: AtomicU8 = AtomicU8::new(LEVEL_UNSET);
+
+/// Sets the level of the logs at runtime, for all the pools, capped by the level feature compiled in, e.g. `log-info`.
+///
+/// Until set, the level is read from the environment variable `TASKORCH_LOG` at the first log,
+/// e.g. `TASKORCH_LOG=debug`, and all the levels compiled in are logged without it or if it is not a level name.
+/// It applies to the logs forwarded by the `log` and `tracing` features as well.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{set_log_level, log_level, LogLevel};
+/// set_log_level(LogLevel::Warn);
+/// // never above the level compiled in, `Off` without any
+/// assert!(log_level() <= LogLevel::Warn);
+/// ```
+pub fn set_log_level(level:LogLevel) {
+    RUNTIME_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// The level of the logs in effect, the lower of the one set at runtime and the one compiled in.
+pub fn log_level()->LogLevel {
+    let mut level = RUNTIME_LEVEL.load(Ordering::Relaxed);
+    if level == LEVEL_UNSET {
+        let from_env = std::env::var(LEVEL_ENV).ok()
+            .and_then(|name|LogLevel::parse(&name))
+            .unwrap_or(LogLevel::Trace) as u8;
+        // unless set meanwhile
+        level = match RUNTIME_LEVEL.compare_exchange(LEVEL_UNSET, from_env, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => from_env,
+            Err(set) => set,
+        };
+    }
+    LogLevel::from_u8(level).min(LEVEL)
+}
+
+pub(crate) fn enabled(level:LogLevel)->bool {
+    level <= log_level()
+}
+
+/// How the logs printed by taskorch are formatted, see [`set_log_format()`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// a line for humans, e.g. `[00.001234 warn  taskorch Th2 taskorch-q1-w0] thread starts ok.`, colored with `log-color`
+    Text,
+    /// one JSON object per line, e.g.
+    /// `{"timestamp":"2025-01-01T08:00:00.001234Z","uptime":"00.001234","level":"warn","module":"taskorch::queue",`
+    /// `"file":"src/queue.rs","line":762,"thread":2,"thread_name":"taskorch-q1-w0","taskid":null,"qid":1,"message":"thread starts ok."}`,
+    /// where `taskid` and `qid` tell the task run by a worker and the queue it serves, `null` out of them.
+    Json,
+}
+
+/// the environment variable read for the format at the first log, `text` or `json`, unless set by [`set_log_format()`].
+pub(crate) const FORMAT_ENV: &str = "TASKORCH_LOG_FORMAT";
+static RUNTIME_FORMAT: AtomicU8 = AtomicU8::new(LEVEL_UNSET);
+
+/// Sets the format of the logs printed by taskorch, for all the pools, [`LogFormat::Text`] by default.
+///
+/// Until set, the format is read from the environment variable `TASKORCH_LOG_FORMAT` at the first log,
+/// e.g. `TASKORCH_LOG_FORMAT=json`. It does not apply to the logs forwarded by the `log` or `tracing` features.
+pub fn set_log_format(format:LogFormat) {
+    RUNTIME_FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+pub fn log_format()->LogFormat {
+    let mut format = RUNTIME_FORMAT.load(Ordering::Relaxed);
+    if format == LEVEL_UNSET {
+        let from_env = match std::env::var(FORMAT_ENV) {
+            Ok(name) if name.trim().eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        } as u8;
+        format = match RUNTIME_FORMAT.compare_exchange(LEVEL_UNSET, from_env, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => from_env,
+            Err(set) => set,
+        };
+    }
+    if format == LogFormat::Json as u8 {LogFormat::Json} else {LogFormat::Text}
+}
+
+thread_local! {
+    /// the task run on the current thread and the queue it serves, as `(taskid,qid)`.
+    static CONTEXT: Cell<(Option<usize>,Option<usize>)> = const { Cell::new((None,None)) };
+}
+
+/// The task and queue of the logs on the current thread until dropped, restoring the outer ones.
+pub(crate) struct LogContext(Option<(Option<usize>,Option<usize>)>);
+
+impl LogContext {
+    pub(crate) fn enter(taskid:Option<usize>, qid:Option<usize>)->Self {
+        if LEVEL == LogLevel::Off {
+            return Self(None);
+        }
+        Self(Some(CONTEXT.replace((taskid,qid))))
+    }
+}
+
+impl Drop for LogContext {
+    fn drop(&mut self) {
+        if let Some(outer) = self.0 {
+            CONTEXT.set(outer);
+        }
+    }
+}
+
+/// the task and queue of the logs on the current thread, as `(taskid,qid)`.
+pub(crate) fn context()->(Option<usize>,Option<usize>) {
+    CONTEXT.get()
+}
+
+/// a log line as a JSON object.
+pub(crate) fn json_line(level:LogLevel, module:&str, file:&str, line:u32, message:&str)->String {
+    use std::fmt::Write;
+    let (taskid,qid) = context();
+    let thread = std::thread::current();
+    let thread_id = format!("{:?}", thread.id());
+    let thread_id: String = thread_id.chars().filter(char::is_ascii_digit).collect();
+    let null_or = |v:Option<usize>|v.map_or_else(||"null".to_string(), |v|v.to_string());
+
+    let mut json = String::with_capacity(256 + message.len());
+    let _ = write!(json, "{{\"timestamp\":\"{}\",\"uptime\":\"{}\",\"level\":\"{}\"", utc_now(), uptime(), level.name());
+    json.push_str(",\"module\":");
+    push_json_str(&mut json, module);
+    json.push_str(",\"file\":");
+    push_json_str(&mut json, file);
+    let _ = write!(json, ",\"line\":{line},\"thread\":{thread_id},\"thread_name\":");
+    match thread.name() {
+        Some(name) => push_json_str(&mut json, name),
+        None => json.push_str("null"),
+    }
+    let _ = write!(json, ",\"taskid\":{},\"qid\":{},\"message\":", null_or(taskid), null_or(qid));
+    push_json_str(&mut json, message);
+    json.push('}');
+    json
+}
+
+/// appends the text as a quoted JSON string.
+fn push_json_str(json:&mut String, text:&str) {
+    use std::fmt::Write;
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => { let _ = write!(json, "\\u{:04x}", c as u32); }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// the current time in UTC as RFC 3339, e.g. `2025-01-01T08:00:00.001234Z`.
+fn utc_now()->String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs();
+    let (year,month,day) = civil_from_days((secs/86400) as i64);
+    let secs = secs%86400;
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z", secs/3600, secs%3600/60, secs%60, now.subsec_micros())
+}
+
+/// the date of the days since 1970-01-01, in the proleptic Gregorian calendar.
+fn civil_from_days(days:i64)->(i64,u32,u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era*146097;
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096)/365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2)/153;
+    let day = (doy - (153*mp + 2)/5 + 1) as u32;
+    let month = if mp < 10 {mp + 3} else {mp - 9} as u32;
+    (yoe + era*400 + (month <= 2) as i64, month, day)
+}
+
+/// Where the logs are written instead of stdout or stderr, see [`set_log_sink()`].
+pub trait LogSink: Send + Sync {
+    /// writes a log line, without the line break nor color.
+    fn write(&self, level:LogLevel, line:&str);
+}
+
+static SINK: RwLock<Option<Arc<dyn LogSink>>> = RwLock::new(None);
+
+/// Writes the logs to the sink instead of stdout or stderr from now on, for all the pools.
+///
+/// Only the logs printed by taskorch go there, not those forwarded by the `log` or `tracing` features.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{set_log_sink, MemorySink};
+/// let sink = MemorySink::new(1000);
+/// set_log_sink(sink.clone());
+/// // ... on failure
+/// for line in sink.lines() {
+///     eprintln!("{line}");
+/// }
+/// ```
+pub fn set_log_sink(sink:impl LogSink + 'static) {
+    *SINK.write().unwrap_or_else(|e|e.into_inner()) = Some(Arc::new(sink));
+}
+
+pub(crate) fn sink()->Option<Arc<dyn LogSink>> {
+    SINK.read().unwrap_or_else(|e|e.into_inner()).clone()
+}
+
+/// Writes the logs to a writer, e.g. a file, flushed at each line.
+pub struct WriterSink<W>(Mutex<W>);
+
+impl<W:Write + Send> WriterSink<W> {
+    pub fn new(writer:W)->Self {
+        Self(Mutex::new(writer))
+    }
+}
+
+impl WriterSink<File> {
+    /// appends to the file, created if it does not exist.
+    pub fn append(path:impl AsRef<Path>)->io::Result<Self> {
+        OpenOptions::new().create(true).append(true).open(path).map(Self::new)
+    }
+}
+
+impl<W:Write + Send> LogSink for WriterSink<W> {
+    fn write(&self, _level:LogLevel, line:&str) {
+        let mut writer = self.0.lock().unwrap_or_else(|e|e.into_inner());
+        let _ = writeln!(writer, "{line}").and_then(|_|writer.flush());
+    }
+}
+
+impl<W> std::fmt::Debug for WriterSink<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"WriterSink{{..}}")
+    }
+}
+
+/// Keeps the latest logs in memory, shared by its clones, e.g. to be dumped when an incident happens.
+#[derive(Clone)]
+pub struct MemorySink(Arc<(usize,Mutex<VecDeque<String>>)>);
+
+impl MemorySink {
+    /// keeps the latest `capacity` lines, the older ones are dropped.
+    pub fn new(capacity:usize)->Self {
+        Self(Arc::new((capacity,Mutex::new(VecDeque::new()))))
+    }
+
+    /// the lines kept, the oldest first.
+    pub fn lines(&self)->Vec<String> {
+        self.0.1.lock().unwrap_or_else(|e|e.into_inner()).iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.0.1.lock().unwrap_or_else(|e|e.into_inner()).clear();
+    }
+}
+
+impl LogSink for MemorySink {
+    fn write(&self, _level:LogLevel, line:&str) {
+        let (capacity,lines) = &*self.0;
+        let mut lines = lines.lock().unwrap_or_else(|e|e.into_inner());
+        if lines.len() >= *capacity {
+            lines.pop_front();
+        }
+        if *capacity > 0 {
+            lines.push_back(line.to_string());
+        }
+    }
+}
+
+impl std::fmt::Debug for MemorySink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"MemorySink{{capacity:{}}}",self.0.0)
+    }
+}
+
 static START_TIME: OnceLock<Instant> = OnceLock::new();
 pub(crate) static LOG_GLOBAL_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
 pub(crate) const NAME:&'static str = "taskorch";
 
 #[allow(unused_macros)]
+#[cfg(not(any(feature = "log", feature = "tracing")))]
 macro_rules! log {
-    ($level:literal $color_head:expr, $color_tail:expr; $($args:tt)*) => {{
-        let msg = log_str!($level $color_head, $color_tail; $($args)*);
-        let lock = crate::log::LOG_GLOBAL_LOCK.get_or_init(||::std::sync::Mutex::new(()));
-        let _guard = lock.lock();
-        log_print!("{}",msg)
+    ($level:tt $color_head:expr, $color_tail:expr; $($args:tt)*) => {{
+        let sink = crate::log::sink();
+        let (color_head,color_tail) = if sink.is_some() {("","")} else {($color_head,$color_tail)};
+        let msg = match crate::log::log_format() {
+            crate::log::LogFormat::Text => log_str!($level color_head, color_tail; $($args)*),
+            crate::log::LogFormat::Json => crate::log::json_line(level_of!($level), module_path!(), file!(), line!(), &format!($($args)*)),
+        };
+        match sink {
+            Some(sink) => sink.write(level_of!($level), &msg),
+            None => {
+                let lock = crate::log::LOG_GLOBAL_LOCK.get_or_init(||::std::sync::Mutex::new(()));
+                let _guard = lock.lock();
+                log_print!("{}",msg)
+            }
+        }
     }}
 }
 
+#[allow(unused_macros)]
+macro_rules! level_of {
+    ("error") => { crate::log::LogLevel::Error };
+    ("warn") => { crate::log::LogLevel::Warn };
+    ("info") => { crate::log::LogLevel::Info };
+    ("debug") => { crate::log::LogLevel::Debug };
+    ("trace") => { crate::log::LogLevel::Trace };
+}
+
+/// forwarded to the `log` facade, the target is the module path, e.g. `taskorch::queue`,
+/// with the task and queue of the current thread as the key-values `taskid` and `qid`.
+#[allow(unused_macros)]
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+macro_rules! log {
+    ("error" $color_head:expr, $color_tail:expr; $($args:tt)*) => { log!(@kv Error; $($args)*) };
+    ("warn" $color_head:expr, $color_tail:expr; $($args:tt)*) => { log!(@kv Warn; $($args)*) };
+    ("info" $color_head:expr, $color_tail:expr; $($args:tt)*) => { log!(@kv Info; $($args)*) };
+    ("debug" $color_head:expr, $color_tail:expr; $($args:tt)*) => { log!(@kv Debug; $($args)*) };
+    ("trace" $color_head:expr, $color_tail:expr; $($args:tt)*) => { log!(@kv Trace; $($args)*) };
+    (@kv $level:ident; $($args:tt)*) => {{
+        let (taskid,qid) = crate::log::context();
+        ::log::log!(::log::Level::$level, taskid, qid; $($args)*)
+    }};
+}
+
+/// forwarded to `tracing` as events, within the spans of `worker_span!` and `task_span!`.
+#[allow(unused_macros)]
+#[cfg(feature = "tracing")]
+macro_rules! log {
+    ("error" $color_head:expr, $color_tail:expr; $($args:tt)*) => { ::tracing::error!($($args)*) };
+    ("warn" $color_head:expr, $color_tail:expr; $($args:tt)*) => { ::tracing::warn!($($args)*) };
+    ("info" $color_head:expr, $color_tail:expr; $($args:tt)*) => { ::tracing::info!($($args)*) };
+    ("debug" $color_head:expr, $color_tail:expr; $($args:tt)*) => { ::tracing::debug!($($args)*) };
+    ("trace" $color_head:expr, $color_tail:expr; $($args:tt)*) => { ::tracing::trace!($($args)*) };
+}
+
+/// enters a `worker` span with the field `qid` until the end of the block with the `tracing` feature,
+/// or the queue of the JSON logs otherwise.
+#[allow(unused_macros)]
+#[cfg(feature = "tracing")]
+macro_rules! worker_span {
+    ($qid:expr) => {
+        let _span = ::tracing::info_span!("worker", qid = $qid).entered();
+    };
+}
+
+#[allow(unused_macros)]
+#[cfg(not(feature = "tracing"))]
+macro_rules! worker_span {
+    ($qid:expr) => {
+        let _context = crate::log::LogContext::enter(None, Some($qid));
+    };
+}
+
+/// enters a `task` span with the fields `taskid` and `qid` until the end of the block with the `tracing` feature,
+/// or the task and queue of the JSON logs otherwise.
+#[allow(unused_macros)]
+#[cfg(feature = "tracing")]
+macro_rules! task_span {
+    ($taskid:expr, $qid:expr) => {
+        let _span = ::tracing::info_span!("task", taskid = $taskid.0.map(|id|id.get()), qid = $qid).entered();
+    };
+}
+
+#[allow(unused_macros)]
+#[cfg(not(feature = "tracing"))]
+macro_rules! task_span {
+    ($taskid:expr, $qid:expr) => {
+        let _context = crate::log::LogContext::enter($taskid.0.map(|id|id.get()), Some($qid));
+    };
+}
+
 #[allow(unused_macros)]
 macro_rules! log_str {
     ($level:literal $color_head:expr, $color_tail:expr; $($args:tt)*) => {{
@@ -97,6 +507,8 @@ macro_rules! log_str {
         let ts = crate::log::uptime();
         let mut thid_buff = [0u8;32];
         let (thid_head,thid_tail) = crate::log::format_concise_current_threadid(&mut thid_buff);
+        let thread = ::std::thread::current();
+        let thname = crate::log::ThreadName(thread.name());
         let filepre = myfilepre!();
         let file = myfile!();
         let (linepre,line) = myline!();
@@ -107,7 +519,7 @@ macro_rules! log_str {
             {level:<5} \
             {name}\
             {filepre}{file}{linepre}{line} \
-            {thid_head}{thid_tail}]\
+            {thid_head}{thid_tail}{thname}]\
             {color_tail} {}",
             format!($($args)*))
     }}
@@ -218,6 +630,10 @@ macro_rules! myline {
     feature = "log-info",
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 )))]
 macro_rules! error {
     ($($args:tt)*) => {
@@ -232,10 +648,16 @@ macro_rules! error {
     feature = "log-info",
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 ))]
 macro_rules! error {
     ($($args:tt)*) => {
-        log!("error" crate::log::COLOR_ERROR, crate::log::COLOR_END; $($args)*)
+        if crate::log::enabled(level_of!("error")) {
+            log!("error" crate::log::COLOR_ERROR, crate::log::COLOR_END; $($args)*)
+        }
     };
 }
 
@@ -247,6 +669,10 @@ macro_rules! error {
     feature = "log-info",
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 )))]
 macro_rules! warn {
     ($($args:tt)*) => {
@@ -260,10 +686,16 @@ macro_rules! warn {
     feature = "log-info",
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 ))]
 macro_rules! warn {
     ($($args:tt)*) => {
-        log!("warn" crate::log::COLOR_WARN, crate::log::COLOR_END; $($args)*)
+        if crate::log::enabled(level_of!("warn")) {
+            log!("warn" crate::log::COLOR_WARN, crate::log::COLOR_END; $($args)*)
+        }
     };
 }
 
@@ -274,6 +706,10 @@ macro_rules! warn {
     feature = "log-info",
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 )))]
 macro_rules! info {
     ($($args:tt)*) => {
@@ -286,10 +722,16 @@ macro_rules! info {
     feature = "log-info",
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 ))]
 macro_rules! info {
     ($($args:tt)*) => {
-        log!("info" crate::log::COLOR_INFO, crate::log::COLOR_END; $($args)*)
+        if crate::log::enabled(level_of!("info")) {
+            log!("info" crate::log::COLOR_INFO, crate::log::COLOR_END; $($args)*)
+        }
     };
 }
 
@@ -300,6 +742,10 @@ macro_rules! info {
 #[cfg(not(any(
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 )))]
 macro_rules! debug {
     ($($args:tt)*) => {
@@ -311,10 +757,16 @@ macro_rules! debug {
 #[cfg(any(
     feature = "log-debug",
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 ))]
 macro_rules! debug {
     ($($args:tt)*) => {
-        log!("debug" crate::log::COLOR_DEBUG, crate::log::COLOR_END; $($args)*)
+        if crate::log::enabled(level_of!("debug")) {
+            log!("debug" crate::log::COLOR_DEBUG, crate::log::COLOR_END; $($args)*)
+        }
     };
 }
 
@@ -324,6 +776,10 @@ macro_rules! debug {
 #[allow(unused_macros)]
 #[cfg(not(any(
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 )))]
 macro_rules! trace {
     ($($args:tt)*) => {
@@ -334,10 +790,16 @@ macro_rules! trace {
 #[allow(unused_macros)]
 #[cfg(any(
     feature = "log-trace",
+    all(
+        any(feature = "log", feature = "tracing"),
+        not(any(feature = "log-error", feature = "log-warn", feature = "log-info", feature = "log-debug", feature = "log-trace")),
+    ),
 ))]
 macro_rules! trace {
     ($($args:tt)*) => {
-        log!("trace" crate::log::COLOR_TRACE, crate::log::COLOR_END; $($args)*)
+        if crate::log::enabled(level_of!("trace")) {
+            log!("trace" crate::log::COLOR_TRACE, crate::log::COLOR_END; $($args)*)
+        }
     };
 }
 
@@ -407,6 +869,81 @@ fn test_log() {
     trace!("message trace");
 }
 
+#[cfg(not(any(feature = "log", feature = "tracing")))]
+#[test]
+fn test_log_sink() {
+    assert_eq!(LogLevel::parse(" Debug"), Some(LogLevel::Debug));
+    assert_eq!(LogLevel::parse("verbose"), None);
+    set_log_level(LogLevel::Trace);
+    assert_eq!(log_level(), LEVEL);
+
+    let sink = MemorySink::new(2);
+    for line in ["a","b","c"] {
+        sink.write(LogLevel::Info, line);
+    }
+    assert_eq!(sink.lines(), ["b","c"]);
+    sink.clear();
+    if LEVEL >= LogLevel::Error {
+        // the other tests may log into it meanwhile
+        let sink = MemorySink::new(usize::MAX);
+        set_log_sink(sink.clone());
+        error!("written to the sink {}", 1);
+        let lines = sink.lines();
+        let line = lines.iter().find(|line|line.ends_with(" written to the sink 1")).unwrap();
+        assert!(line.contains("error") && !line.contains('\x1b'));
+    }
+}
+
+#[test]
+fn test_json_line() {
+    assert_eq!(civil_from_days(0), (1970,1,1));
+    assert_eq!(civil_from_days(11016), (2000,2,29));
+    assert_eq!(civil_from_days(20000), (2024,10,4));
+
+    let mut json = String::new();
+    push_json_str(&mut json, "a \"b\"\\\n\u{1}é");
+    assert_eq!(json, r#""a \"b\"\\\n\u0001é""#);
+
+    let _context = LogContext::enter(Some(7), Some(1));
+    let line = json_line(LogLevel::Warn, module_path!(), file!(), 42, "hello");
+    assert!(line.starts_with("{\"timestamp\":\""));
+    let tail = r#","level":"warn","module":"taskorch::log","file":"src/log.rs","line":42,"thread":"#;
+    assert!(line.contains(tail), "{line}");
+    let (taskid,qid) = if LEVEL == LogLevel::Off {("null","null")} else {("7","1")};
+    assert!(line.ends_with(&format!(",\"taskid\":{taskid},\"qid\":{qid},\"message\":\"hello\"}}")), "{line}");
+}
+
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+#[test]
+fn test_log_bridge() {
+    use ::log::kv::Key;
+    struct Capture(Mutex<Vec<(::log::Level,String,String,String)>>);
+    impl ::log::Log for Capture {
+        fn enabled(&self, _:&::log::Metadata)->bool { true }
+        fn log(&self, record:&::log::Record) {
+            let kv = |key|record.key_values().get(Key::from_str(key)).map_or_else(||"-".to_string(), |v|v.to_string());
+            let kvs = format!("taskid={},qid={}", kv("taskid"), kv("qid"));
+            self.0.lock().unwrap().push((record.level(), record.target().to_string(), record.args().to_string(), kvs));
+        }
+        fn flush(&self) {}
+    }
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+    ::log::set_logger(&CAPTURE).unwrap();
+    ::log::set_max_level(::log::LevelFilter::Trace);
+
+    let n = 42;
+    error!("bridged to the logger {n}");
+    {
+        let _context = LogContext::enter(Some(7), Some(1));
+        warn!("in a task");
+    }
+    let records = CAPTURE.0.lock().unwrap();
+    let record = records.iter().find(|r|r.2 == "bridged to the logger 42").unwrap();
+    assert_eq!((record.0, record.1.as_str()), (::log::Level::Error, "taskorch::log"));
+    let record = records.iter().find(|r|r.2 == "in a task").unwrap();
+    assert_eq!(record.3, "taskid=7,qid=1");
+}
+
 fn format_threadid(buf:&mut[u8;32])->usize {
     use std::io::{Cursor,Write};
     let mut cursor = Cursor::new(&mut buf[..]);
@@ -450,6 +987,18 @@ pub(crate) fn format_concise_current_threadid(buff:&mut ThreadIdBuf)->(&str,&str
     concise_threadid(buff, len)
 }
 
+/// displays the name of a thread after a space, nothing if unnamed,
+/// e.g. ` taskorch-q1-w0` tells the queue served by a worker thread.
+pub(crate) struct ThreadName<'a>(pub(crate) Option<&'a str>);
+
+impl std::fmt::Display for ThreadName<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(name) => write!(f," {name}"),
+            None => Ok(()),
+        }
+    }
+}
 
 #[test]
 fn test_format_threadid_by_cursor() {
@@ -461,6 +1010,8 @@ fn test_format_threadid_by_cursor() {
     println!("{len} {s} {head} {tail}");
 
     format_concise_current_threadid(&mut buf);
+    assert_eq!(ThreadName(Some("io-w0")).to_string(), " io-w0");
+    assert_eq!(ThreadName(None).to_string(), "");
 }
 
 #[test]
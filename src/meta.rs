@@ -1,5 +1,5 @@
 
-use crate::cond::CondAddr;
+use crate::cond::{CondAddr, Section, TaskId};
 
 
 #[doc(hidden)]
@@ -241,6 +241,11 @@ pub trait TupleCondAddr {
     type E1;
     type TCA; // CondAddrTuple
     const ONETOONE: Self::TCA;
+
+    /// visits each address bound to by `TaskNeed::bind_all_to()`,
+    /// with its task, whether it is an input, its index and its type name.
+    #[doc(hidden)]
+    fn each_addr(_tca:&Self::TCA, _f:&mut dyn FnMut(TaskId,bool,u8,&'static str)) {}
 }
 
 impl TupleCondAddr for () {
@@ -260,14 +265,24 @@ impl<T1> TupleCondAddr for (T1,) {
     type E1 = T1;
     type TCA = (CondAddr<T1>,);
     const ONETOONE: Self::TCA = (CondAddr::<T1>::new::<0>(),);
+    fn each_addr(tca:&Self::TCA, f:&mut dyn FnMut(TaskId,bool,u8,&'static str)) {
+        visit(&tca.0, f);
+    }
+}
+
+fn visit<T>(ca:&CondAddr<T>, f:&mut dyn FnMut(TaskId,bool,u8,&'static str)) {
+    f(ca.taskid(), *ca.section() == Section::Input, ca.argidx().i(), std::any::type_name::<T>());
 }
 
 macro_rules! impl_tuple_condaddr {
-    ($($n:literal $T:ident),+) => {
+    ($($n:tt $T:ident),+) => {
         impl<$($T),+> TupleCondAddr for ($($T,)+) {
             type E1 = T1;
             type TCA = ($(CondAddr<$T>,)+);
             const ONETOONE:Self::TCA = ($(CondAddr::<$T>::new::<$n>(),)+);
+            fn each_addr(tca:&Self::TCA, f:&mut dyn FnMut(TaskId,bool,u8,&'static str)) {
+                $(visit(&tca.$n, f);)+
+            }
         }
     };
 }
@@ -0,0 +1,163 @@
+//! A bounded lock-free multi-producer multi-consumer ring,
+//! used by `Queue::lock_free()` as the fast path of the shared deque.
+//!
+//! Each slot carries a sequence number telling whether it is ready to be written or read
+//! in the current lap, so producers and consumers only contend on a CAS of the tail or the head.
+
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct Slot<T> {
+    seq: AtomicUsize,
+    val: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// keeps the head and the tail in different cache lines
+#[repr(align(64))]
+struct Padded(AtomicUsize);
+
+pub(crate) struct Ring<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    /// the position of the next pop
+    head: Padded,
+    /// the position of the next push
+    tail: Padded,
+}
+
+// the values are moved in and out through the slots, guarded by their sequence numbers.
+unsafe impl<T:Send> Send for Ring<T> {}
+unsafe impl<T:Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    /// the capacity is rounded up to a power of 2.
+    pub(crate) fn new(capacity:usize)->Self {
+        let capacity = capacity.max(2).next_power_of_two();
+        let slots = (0..capacity)
+            .map(|i|Slot { seq: AtomicUsize::new(i), val: UnsafeCell::new(MaybeUninit::uninit()) })
+            .collect();
+        Self { slots, mask: capacity - 1, head: Padded(AtomicUsize::new(0)), tail: Padded(AtomicUsize::new(0)) }
+    }
+
+    /// pushes the value, or gives it back if the ring is full.
+    pub(crate) fn push(&self, v:T)->Result<(),T> {
+        let mut pos = self.tail.0.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos) as isize;
+            if diff == 0 {
+                match self.tail.0.compare_exchange_weak(pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        // the slot is owned by this producer until the seq is published.
+                        unsafe { (*slot.val.get()).write(v) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(cur) => pos = cur,
+                }
+            } else if diff < 0 {
+                // not read yet in the last lap
+                return Err(v);
+            } else {
+                pos = self.tail.0.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// pops the oldest value, `None` if the ring is empty.
+    pub(crate) fn pop(&self)->Option<T> {
+        let mut pos = self.head.0.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq.wrapping_sub(pos.wrapping_add(1)) as isize;
+            if diff == 0 {
+                match self.head.0.compare_exchange_weak(pos, pos.wrapping_add(1), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => {
+                        // the slot is owned by this consumer until the seq is published.
+                        let v = unsafe { (*slot.val.get()).assume_init_read() };
+                        slot.seq.store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+                        return Some(v);
+                    }
+                    Err(cur) => pos = cur,
+                }
+            } else if diff < 0 {
+                // not written yet in this lap
+                return None;
+            } else {
+                pos = self.head.0.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// whether the next value to pop has not been published, may be stale under contention.
+    pub(crate) fn is_empty(&self)->bool {
+        let pos = self.head.0.load(Ordering::SeqCst);
+        let seq = self.slots[pos & self.mask].seq.load(Ordering::SeqCst);
+        (seq.wrapping_sub(pos.wrapping_add(1)) as isize) < 0
+    }
+
+    /// the count of the values, may be stale under contention.
+    pub(crate) fn len(&self)->usize {
+        let head = self.head.0.load(Ordering::Relaxed);
+        let tail = self.tail.0.load(Ordering::Relaxed);
+        (tail.wrapping_sub(head) as isize).max(0) as usize
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[test]
+fn test_ring() {
+    use std::{sync::Arc, thread};
+    let ring = Ring::new(3);
+    assert!(ring.is_empty());
+    for i in 0..4 {
+        assert_eq!(ring.push(i), Ok(()));
+    }
+    assert_eq!(ring.push(4), Err(4));
+    assert_eq!(ring.len(), 4);
+    assert_eq!(ring.pop(), Some(0));
+    assert_eq!(ring.push(4), Ok(()));
+    assert_eq!((1..5).map(|_|ring.pop().unwrap()).collect::<Vec<_>>(), vec![1,2,3,4]);
+    assert_eq!(ring.pop(), None);
+
+    // every value is popped exactly once
+    let ring = Arc::new(Ring::new(64));
+    let producers: Vec<_> = (0..4).map(|p|{
+        let ring = ring.clone();
+        thread::spawn(move||for i in 0..1000 {
+            let mut v = p*1000 + i;
+            while let Err(back) = ring.push(v) {
+                v = back;
+                thread::yield_now();
+            }
+        })
+    }).collect();
+    let consumers: Vec<_> = (0..4).map(|_|{
+        let ring = ring.clone();
+        thread::spawn(move||{
+            let mut got = Vec::new();
+            while got.len() < 1000 {
+                match ring.pop() {
+                    Some(v) => got.push(v),
+                    None => thread::yield_now(),
+                }
+            }
+            got
+        })
+    }).collect();
+    producers.into_iter().for_each(|p|p.join().unwrap());
+    let mut all: Vec<usize> = consumers.into_iter().flat_map(|c|c.join().unwrap()).collect();
+    all.sort();
+    assert_eq!(all, (0..4000).collect::<Vec<_>>());
+    drop(Arc::try_unwrap(ring).ok().unwrap());
+}
@@ -0,0 +1,249 @@
+//! # `observe` module
+//!
+//! Hooks into the lifecycle of the tasks of a pool, see [`Pool::add_observer()`](crate::Pool::add_observer).
+//!
+//! An [`Observer`] is called on the thread where each event happens: the submitting thread,
+//! the worker running a task or delivering its result, or the timer thread.
+//! The events of a task are seen in order on each thread, but those raised by different threads may interleave,
+//! e.g. a task may be started by a worker before its submitter has notified `submitted`.
+//!
+//! No lock of the pool is held while an observer is called, so it may submit tasks,
+//! but it should be quick and must not panic, it would take down the thread calling it.
+//! Without any observer added, each event costs a relaxed atomic load.
+
+use std::{
+    any::type_name,
+    sync::{atomic::{AtomicBool, Ordering}, Arc, RwLock},
+    time::Duration,
+};
+
+use crate::cond::{CondAddr, TaskId};
+use crate::fault::TaskPanic;
+
+/// Receives the lifecycle events of the tasks of a pool, all the callbacks do nothing by default.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{Pool, Queue, TaskBuildNew, TaskId, Observer, Delivery};
+/// # use std::sync::{Arc, Mutex};
+/// #[derive(Default)]
+/// struct Audit(Mutex<Vec<String>>);
+///
+/// impl Observer for Audit {
+///     fn delivered(&self, delivery:&Delivery) {
+///         self.0.lock().unwrap().push(format!("{:?} -> {:?}.{}", delivery.from(), delivery.to(), delivery.argidx()));
+///     }
+///     fn finished(&self, taskid:TaskId, _qid:usize, _run:std::time::Duration) {
+///         self.0.lock().unwrap().push(format!("{taskid:?} finished"));
+///     }
+/// }
+///
+/// let mut pool = Pool::new();
+/// let audit = Arc::new(Audit::default());
+/// pool.add_observer(audit.clone());
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+/// let print = submitter.submit((|a:i32|println!("{a}")).into_exit_task()).take();
+/// submitter.submit((||1).into_task().bind_to(print.input_ca::<0>()));
+/// pool.spawn_thread_for(qid);
+/// pool.join();
+/// assert_eq!(audit.0.lock().unwrap().len(), 3);
+/// ```
+pub trait Observer: Send + Sync {
+    /// the task has been put into the queue `qid`, or into the waitQueue if it has conditions.
+    fn submitted(&self, _taskid:TaskId, _qid:usize, _conditional:bool) {}
+
+    /// a result has been delivered to a condition of a waiting task.
+    fn delivered(&self, _delivery:&Delivery) {}
+
+    /// a result could not be delivered, and is dropped.
+    fn delivery_failed(&self, _delivery:&Delivery, _error:DeliveryError) {}
+
+    /// all the conditions of the task have arrived, and it has been put into the queue `qid`.
+    fn released(&self, _taskid:TaskId, _qid:usize) {}
+
+    /// a worker serving the queue `qid` starts running the task.
+    fn started(&self, _taskid:TaskId, _qid:usize) {}
+
+    /// the task has returned and its result has been passed on, `run` long after it started.
+    /// a task with a retry policy finishes once, by its last attempt.
+    fn finished(&self, _taskid:TaskId, _qid:usize, _run:Duration) {}
+
+    /// the task panicked, see also [`Pool::on_panic()`](crate::Pool::on_panic).
+    fn panicked(&self, _panic:&TaskPanic) {}
+}
+
+impl<O:Observer + ?Sized> Observer for Arc<O> {
+    fn submitted(&self, taskid:TaskId, qid:usize, conditional:bool) {
+        (**self).submitted(taskid, qid, conditional)
+    }
+    fn delivered(&self, delivery:&Delivery) {
+        (**self).delivered(delivery)
+    }
+    fn delivery_failed(&self, delivery:&Delivery, error:DeliveryError) {
+        (**self).delivery_failed(delivery, error)
+    }
+    fn released(&self, taskid:TaskId, qid:usize) {
+        (**self).released(taskid, qid)
+    }
+    fn started(&self, taskid:TaskId, qid:usize) {
+        (**self).started(taskid, qid)
+    }
+    fn finished(&self, taskid:TaskId, qid:usize, run:Duration) {
+        (**self).finished(taskid, qid, run)
+    }
+    fn panicked(&self, panic:&TaskPanic) {
+        (**self).panicked(panic)
+    }
+}
+
+/// A result of a task sent to a condition of another one, see [`TaskNeed::bind_to()`](crate::TaskNeed::bind_to).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delivery {
+    from: TaskId,
+    to: TaskId,
+    argidx: u8,
+    typename: &'static str,
+}
+
+impl Delivery {
+    pub(crate) fn of<T>(ca:&CondAddr<T>, from:TaskId)->Self {
+        Self { from, to: ca.taskid(), argidx: ca.argidx().i(), typename: type_name::<T>() }
+    }
+
+    /// the task producing the result
+    pub fn from(&self)->TaskId {
+        self.from
+    }
+
+    /// the task receiving it
+    pub fn to(&self)->TaskId {
+        self.to
+    }
+
+    /// the index of the condition
+    pub fn argidx(&self)->u8 {
+        self.argidx
+    }
+
+    /// the type of the result
+    pub fn typename(&self)->&'static str {
+        self.typename
+    }
+}
+
+/// Why a result could not be delivered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeliveryError {
+    /// the address does not point to any task.
+    NoTask,
+    /// the address is not an input of the task.
+    NotInput,
+    /// the task is not waiting: it has not been submitted yet, or it has been released, canceled or expired.
+    NotWaiting,
+    /// the task has no conditions.
+    NoConditions,
+    /// the task has no condition at this index.
+    NoSuchInput,
+    /// the condition has another type.
+    TypeMismatch { expected: &'static str },
+}
+
+type ObserverList = Arc<[Arc<dyn Observer>]>;
+
+/// The observers of a pool, shared with its submitters and threads.
+#[derive(Clone, Default)]
+pub(crate) struct Observers(Arc<(AtomicBool,RwLock<ObserverList>)>);
+
+impl Observers {
+    pub(crate) fn add(&self, observer:Arc<dyn Observer>) {
+        let mut observers = self.0.1.write().unwrap();
+        *observers = observers.iter().cloned().chain([observer]).collect();
+        self.0.0.store(true, Ordering::Release);
+    }
+
+    /// calls each observer, the list is copied first so an observer may add another one.
+    pub(crate) fn notify(&self, f:impl Fn(&dyn Observer)) {
+        if !self.0.0.load(Ordering::Relaxed) {
+            return;
+        }
+        let observers = self.0.1.read().unwrap().clone();
+        for observer in observers.iter() {
+            f(&**observer);
+        }
+    }
+}
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Observers{{{}}}",self.0.1.read().unwrap().len())
+    }
+}
+
+#[test]
+fn test_observer() {
+    use crate::{Pool, Queue, TaskBuildNew};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Events(Mutex<Vec<String>>);
+    impl Events {
+        fn push(&self, event:String) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+    impl Observer for Events {
+        fn submitted(&self, taskid:TaskId, _qid:usize, conditional:bool) {
+            self.push(format!("submitted {taskid:?} {conditional}"));
+        }
+        fn delivered(&self, d:&Delivery) {
+            self.push(format!("delivered {:?}->{:?}.{} {}", d.from(), d.to(), d.argidx(), d.typename()));
+        }
+        fn delivery_failed(&self, d:&Delivery, error:DeliveryError) {
+            self.push(format!("failed {:?}->{:?} {error:?}", d.from(), d.to()));
+        }
+        fn released(&self, taskid:TaskId, _qid:usize) {
+            self.push(format!("released {taskid:?}"));
+        }
+        fn started(&self, taskid:TaskId, _qid:usize) {
+            self.push(format!("started {taskid:?}"));
+        }
+        fn finished(&self, taskid:TaskId, _qid:usize, _run:Duration) {
+            self.push(format!("finished {taskid:?}"));
+        }
+        fn panicked(&self, panic:&TaskPanic) {
+            self.push(format!("panicked {:?} {}", panic.taskid(), panic.message()));
+        }
+    }
+
+    let mut pool = Pool::new();
+    let events = Arc::new(Events::default());
+    pool.add_observer(events.clone());
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+
+    // the thread exits after it, even if it panicked
+    let add = submitter.submit((|a:i32,b:u8|assert!(a > b as i32, "too small")).into_exit_task()).take();
+    let add_id = add.taskid();
+    let source = TaskId::new(9001);
+    submitter.submit((||1,source).into_task().bind_to(add.input_ca::<0>()));
+    // no such task waiting
+    let gone = submitter.submit((|_:u8|{}).into_task()).take();
+    submitter.cancel(gone.taskid());
+    let bad = TaskId::new(9002);
+    submitter.submit((||2u8,bad).into_task().bind_to(gone.input_ca::<0>()));
+    submitter.submit((||2u8).into_task().bind_to(add.input_ca::<1>()));
+    pool.spawn_thread_for(qid);
+    pool.join();
+
+    let events = events.0.lock().unwrap();
+    let position = |event:&str|events.iter().position(|e|e == event).unwrap_or_else(||panic!("{event} not found in {events:?}"));
+    assert!(position(&format!("submitted {add_id:?} true")) < position(&format!("released {add_id:?}")));
+    assert!(position(&format!("started {source:?}")) < position(&format!("delivered {source:?}->{add_id:?}.0 i32")));
+    assert!(position(&format!("delivered {source:?}->{add_id:?}.0 i32")) < position(&format!("finished {source:?}")));
+    position(&format!("failed {bad:?}->{:?} NotWaiting", gone.taskid()));
+    assert!(position(&format!("released {add_id:?}")) < position(&format!("started {add_id:?}")));
+    position(&format!("panicked {add_id:?} too small"));
+    assert!(!events.contains(&format!("finished {add_id:?}")));
+    assert_eq!(events.iter().filter(|e|e.starts_with("submitted")).count(), 5);
+}
@@ -0,0 +1,325 @@
+//! # `periodic` module
+//!
+//! Recurring tasks: a task factory is called on each tick of a [`Schedule`],
+//! and the task it builds is submitted, see [`TaskSubmitter::submit_periodic()`](crate::TaskSubmitter::submit_periodic).
+//!
+//! The ticks are driven by the timer thread of the `Pool`, no worker thread is occupied between them.
+//! The schedule is stopped by [`PeriodicHandle::stop()`], or when the `Pool` is joined.
+//! A panic raised by the task factory only skips its tick.
+
+use std::{
+    fmt::Debug,
+    sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::timer::TimerDo;
+
+/// When the ticks of a recurring task happen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// A tick every period, counted from the first tick, whether the previous task has run or not.
+    /// Missed ticks are skipped rather than bursting.
+    FixedRate(Duration),
+    /// The next tick happens the delay after the previous task has run, or has been dropped without running,
+    /// e.g. it panicked, was canceled or expired.
+    FixedDelay(Duration),
+    /// A tick at each minute matched by the cron expression, in UTC.
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Parses a cron expression, see [`Cron::parse()`].
+    pub fn cron(expr:&str)->Result<Self,CronError> {
+        Cron::parse(expr).map(Self::Cron)
+    }
+
+    /// the instant of the first tick, with its wall-clock second since the unix epoch.
+    pub(crate) fn first(&self, now:Instant)->Option<(Instant,u64)> {
+        match self {
+            Self::FixedRate(period) | Self::FixedDelay(period) => Some(tick_at(now + *period)),
+            Self::Cron(cron) => cron.next_instant(unix_secs(now)),
+        }
+    }
+
+    /// the tick following the one fired at `at`, the wall-clock second `secs`,
+    /// `None` for `FixedDelay` which is scheduled when the task has run.
+    pub(crate) fn next(&self, at:Instant, secs:u64)->Option<(Instant,u64)> {
+        match self {
+            Self::FixedRate(period) => {
+                let now = Instant::now();
+                let mut next = at + *period;
+                while next <= now {
+                    next += *period;
+                }
+                Some(tick_at(next))
+            }
+            Self::FixedDelay(_) => None,
+            // counted from the tick fired rather than from now,
+            // so a tick fired a bit early is not fired twice.
+            Self::Cron(cron) => cron.next_instant(secs),
+        }
+    }
+}
+
+/// the wall-clock second since the unix epoch at `at`.
+fn unix_secs(at:Instant)->u64 {
+    let (now,sys) = (Instant::now(), SystemTime::now());
+    let wall = match at.checked_duration_since(now) {
+        Some(ahead) => sys + ahead,
+        None => sys - now.duration_since(at),
+    };
+    wall.duration_since(UNIX_EPOCH).map_or(0, |d|d.as_secs())
+}
+
+/// the tick at `at`, with its wall-clock second.
+pub(crate) fn tick_at(at:Instant)->(Instant,u64) {
+    (at, unix_secs(at))
+}
+
+/// Error type for invalid cron expressions
+#[derive(Debug, PartialEq)]
+pub enum CronError {
+    /// the expression does not have 5 fields, holds the count found.
+    FieldCount(usize),
+    /// the field is malformed or out of range, holds the field name and its text.
+    InvalidField(&'static str, String),
+}
+
+const FIELDS:[(&str,u32,u32);5] = [
+    ("minute",0,59),
+    ("hour",0,23),
+    ("day of month",1,31),
+    ("month",1,12),
+    ("day of week",0,7),
+];
+
+/// A cron expression with the 5 standard fields: `minute hour day-of-month month day-of-week`.
+///
+/// Each field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`, or a list of them separated by `,`.
+/// Day of week is 0-7, both 0 and 7 are Sunday.
+/// As in the classic cron, if both day of month and day of week are restricted,
+/// a day matching either of them is matched.
+#[derive(Clone, PartialEq)]
+pub struct Cron {
+    expr: String,
+    // bit i is set if value i is matched
+    fields: [u64;5],
+}
+
+impl Cron {
+    /// Parses a cron expression.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Cron,CronError};
+    /// // every 15 minutes during working hours on weekdays
+    /// assert!(Cron::parse("*/15 9-17 * * 1-5").is_ok());
+    /// assert_eq!(Cron::parse("* * *"), Err(CronError::FieldCount(3)));
+    /// assert_eq!(Cron::parse("60 * * * *"), Err(CronError::InvalidField("minute","60".to_string())));
+    /// ```
+    pub fn parse(expr:&str)->Result<Self,CronError> {
+        let parts: Vec<&str> = expr.split_whitespace().collect();
+        if parts.len() != FIELDS.len() {
+            return Err(CronError::FieldCount(parts.len()));
+        }
+        let mut fields = [0u64;5];
+        for (i,(part,&(name,min,max))) in parts.iter().zip(FIELDS.iter()).enumerate() {
+            fields[i] = parse_field(part, min, max)
+                .ok_or_else(||CronError::InvalidField(name, part.to_string()))?;
+        }
+        // 7 is also Sunday
+        if fields[4] & (1<<7) != 0 {
+            fields[4] = (fields[4] | 1) & !(1<<7);
+        }
+        Ok(Self { expr: expr.to_string(), fields })
+    }
+
+    fn matches(&self, field:usize, v:u32)->bool {
+        self.fields[field] & (1<<v) != 0
+    }
+
+    fn is_any(&self, field:usize)->bool {
+        let (_,min,max) = FIELDS[field];
+        let max = if field == 4 { 6 } else { max };
+        (min..=max).all(|v|self.matches(field, v))
+    }
+
+    fn matches_day(&self, days:i64)->bool {
+        let (_,month,day) = civil_from_days(days);
+        if !self.matches(3, month) {
+            return false;
+        }
+        // 1970-01-01 is Thursday
+        let weekday = (days + 4).rem_euclid(7) as u32;
+        match (self.is_any(2), self.is_any(4)) {
+            (false,false) => self.matches(2, day) || self.matches(4, weekday),
+            _ => self.matches(2, day) && self.matches(4, weekday),
+        }
+    }
+
+    /// the first matched minute strictly after `secs`, in seconds since the unix epoch.
+    /// returns `None` if no day matches in the next 5 years, e.g. `0 0 30 2 *`.
+    pub(crate) fn next_after(&self, secs:u64)->Option<u64> {
+        let start = secs / 60 + 1;
+        let (mut days, mut minute) = ((start / 1440) as i64, (start % 1440) as u32);
+        for _ in 0..366*5 {
+            if self.matches_day(days) {
+                let found = (minute..1440).find(|m|self.matches(1, m/60) && self.matches(0, m%60));
+                if let Some(m) = found {
+                    return Some((days as u64 * 1440 + m as u64) * 60);
+                }
+            }
+            days += 1;
+            minute = 0;
+        }
+        None
+    }
+
+    /// the first matched minute strictly after `secs`, as an instant and in seconds since the unix epoch.
+    fn next_instant(&self, secs:u64)->Option<(Instant,u64)> {
+        let next = self.next_after(secs)?;
+        let wait = (UNIX_EPOCH + Duration::from_secs(next)).duration_since(SystemTime::now()).unwrap_or_default();
+        Some((Instant::now() + wait, next))
+    }
+}
+
+impl Debug for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Cron({:?})",self.expr)
+    }
+}
+
+fn parse_field(field:&str, min:u32, max:u32)->Option<u64> {
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range,step) = match item.split_once('/') {
+            Some((range,step)) => (range, step.parse::<u32>().ok().filter(|s|*s>0)?),
+            None => (item, 1),
+        };
+        let (lo,hi) = match range {
+            "*" => (min,max),
+            _ => match range.split_once('-') {
+                Some((lo,hi)) => (lo.parse().ok()?, hi.parse().ok()?),
+                None => {
+                    let v = range.parse().ok()?;
+                    // `a/n` means from a to the max
+                    (v, if step > 1 { max } else { v })
+                }
+            },
+        };
+        if lo < min || hi > max || lo > hi {
+            return None;
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            bits |= 1<<v;
+        }
+    }
+    Some(bits)
+}
+
+/// (year, month, day) of the days since 1970-01-01, in the proleptic Gregorian calendar.
+fn civil_from_days(days:i64)->(i64,u32,u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2) / 153;
+    let day = (doy - (153*mp + 2)/5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year,month,day)
+}
+
+struct PeriodicState {
+    stopped: AtomicBool,
+    ticks: AtomicU64,
+}
+
+/// A handle to a recurring task, to stop it or count its ticks.
+///
+/// Dropping the handle does not stop the schedule.
+#[derive(Clone)]
+pub struct PeriodicHandle(Arc<PeriodicState>);
+
+impl PeriodicHandle {
+    pub(crate) fn new()->Self {
+        Self(Arc::new(PeriodicState { stopped: AtomicBool::new(false), ticks: AtomicU64::new(0) }))
+    }
+
+    /// Stops the schedule, no more tasks are submitted.
+    /// The tasks already submitted are not affected.
+    pub fn stop(&self) {
+        self.0.stopped.store(true, Ordering::Release);
+    }
+
+    /// whether the schedule has been stopped
+    pub fn is_stopped(&self)->bool {
+        self.0.stopped.load(Ordering::Acquire)
+    }
+
+    /// the count of the tasks submitted so far
+    pub fn ticks(&self)->u64 {
+        self.0.ticks.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn tick(&self) {
+        self.0.ticks.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+impl Debug for PeriodicHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"PeriodicHandle{{ticks:{},stopped:{}}}",self.ticks(),self.is_stopped())
+    }
+}
+
+/// The state shared by the ticks of a recurring task.
+pub(crate) struct Periodic<F> {
+    pub(crate) schedule: Schedule,
+    pub(crate) factory: Mutex<F>,
+    pub(crate) handle: PeriodicHandle,
+}
+
+/// Runs the job when dropped, used to schedule the next `FixedDelay` tick
+/// whether the task has run or has been dropped.
+pub(crate) struct OnDrop(pub(crate) Option<Box<TimerDo>>);
+
+impl Drop for OnDrop {
+    fn drop(&mut self) {
+        if let Some(job) = self.0.take() {
+            job();
+        }
+    }
+}
+
+#[test]
+fn test_cron() {
+    let cron = Cron::parse("*/15 9-17 * * 1-5").unwrap();
+    // 2024-01-01 00:00:00 UTC, Monday
+    let monday = 1704067200u64;
+    assert_eq!(civil_from_days((monday/86400) as i64), (2024,1,1));
+    assert_eq!(cron.next_after(monday), Some(monday + 9*3600));
+    assert_eq!(cron.next_after(monday + 9*3600), Some(monday + 9*3600 + 15*60));
+    assert_eq!(cron.next_after(monday + 9*3600 + 59), Some(monday + 9*3600 + 15*60));
+    assert_eq!(cron.next_after(monday + 17*3600 + 45*60), Some(monday + 86400 + 9*3600));
+    // friday evening to monday morning
+    let friday = monday + 4*86400;
+    assert_eq!(cron.next_after(friday + 18*3600), Some(monday + 7*86400 + 9*3600));
+
+    // sunday as 7, or the 13th
+    let cron = Cron::parse("0 0 13 * 7").unwrap();
+    assert_eq!(cron.next_after(monday), Some(monday + 6*86400));
+    assert_eq!(cron.next_after(monday + 6*86400), Some(monday + 12*86400));
+
+    assert_eq!(Cron::parse("0 0 30 2 *").unwrap().next_after(monday), None);
+
+    // counted from the tick fired, even if it fired before its wall-clock second
+    let every = Schedule::cron("* * * * *").unwrap();
+    let fired = (unix_secs(Instant::now()) / 60 + 60) * 60;
+    assert_eq!(every.next(Instant::now(), fired).map(|(_,secs)|secs), Some(fired + 60));
+    assert_eq!(Cron::parse("1-2/0 * * * *"), Err(CronError::InvalidField("minute","1-2/0".to_string())));
+    assert_eq!(Cron::parse("* * 0 * *"), Err(CronError::InvalidField("day of month","0".to_string())));
+    assert_eq!(Cron::parse("5/20 * * * *").unwrap().fields[0], (1<<5)|(1<<25)|(1<<45));
+}
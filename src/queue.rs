@@ -1,11 +1,21 @@
 use std::{
-    any::{type_name, Any}, collections::{HashMap, VecDeque}, fmt::Debug, num::NonZeroUsize, sync::{
-        atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex
-    }, thread
+    any::{type_name, Any}, cell::RefCell, collections::{BTreeMap, HashMap, VecDeque}, fmt::Debug, num::NonZeroUsize, time::{Duration, Instant}, sync::{
+        atomic::{fence, AtomicBool, AtomicU8, AtomicUsize, Ordering}, Arc, Condvar, Mutex, RwLock
+    }, thread, io
 };
 
 use crate::cond::{CondAddr, Section, TaskId};
-use crate::{task::{Kind, Task}, Jhandle};
+use crate::mpmc::Ring;
+use crate::scaling::Scaler;
+use crate::fault::{run_isolated, Handler, PanicReport};
+use crate::retry::Retried;
+use crate::expire::{ExpireAction, ExpirePolicy, ExpireReport, TaskExpired};
+use crate::graph::{inputs, Graph, Producers};
+use crate::export::GraphExport;
+use crate::stats::{Histogram, QueueMeter, QueueStats, Recorder, Workers};
+use crate::observe::{Delivery, DeliveryError, Observers};
+use crate::task::TaskBuildNew;
+use crate::{task::{Kind, Priority, Task}, Jhandle};
 
 pub(crate) type TaskDo = dyn Task+Send;
 pub(crate) type PostDo = dyn FnOnce(Box<dyn Any>) + Send;
@@ -18,52 +28,625 @@ pub(crate) enum Inserted {
 }
 
 
+/// A task put into a queue, with the time it was put in for aging.
+struct Queued {
+    at: Instant,
+    task: Box<TaskDo>,
+    postdo: Box<PostDo>,
+}
+
+/// The tasks in a queue, a FIFO per priority.
+pub(crate) struct Tasks {
+    buckets: BTreeMap<Priority,VecDeque<Queued>>,
+    len: usize,
+    /// a waiting task gains one priority level per step
+    aging: Option<Duration>,
+    /// whether the workers serving the queue have local deques and steal from each other
+    stealing: bool,
+    /// the max count of tasks submitted into the queue, see `Queue::bounded()`
+    capacity: Option<usize>,
+}
+
+impl Tasks {
+    fn new(aging:Option<Duration>)->Self {
+        Self { buckets: BTreeMap::new(), len: 0, aging, stealing: false, capacity: None }
+    }
+
+    pub(crate) fn is_empty(&self)->bool {
+        self.len == 0
+    }
+
+    fn push_back(&mut self, (task,postdo):(Box<TaskDo>,Box<PostDo>)) {
+        self.push_queued(Queued { at: Instant::now(), task, postdo });
+    }
+
+    /// keeps the time the task was put into the queue, e.g. when it overflows the lock-free ring.
+    fn push_queued(&mut self, queued:Queued) {
+        self.buckets.entry(queued.task.priority()).or_default().push_back(queued);
+        self.len += 1;
+    }
+
+    pub(crate) fn pop_front(&mut self)->Option<(Box<TaskDo>,Box<PostDo>)> {
+        self.pop_queued().map(|Queued { task, postdo, .. }|(task,postdo))
+    }
+
+    /// pops the task of the highest priority, raised by its age if aging is enabled.
+    /// the oldest one runs first among the same priority.
+    fn pop_queued(&mut self)->Option<Queued> {
+        let p = match self.aging {
+            None => *self.buckets.keys().next_back()?,
+            Some(step) => {
+                let now = Instant::now();
+                // the front is the oldest, so the highest aged priority in its bucket.
+                let aged = |p:&Priority,q:&Queued| {
+                    let levels = (now - q.at).as_nanos() / step.as_nanos().max(1);
+                    (p.0 as u128 + levels, std::cmp::Reverse(q.at))
+                };
+                let (p,_) = self.buckets
+                    .iter()
+                    .filter_map(|(p,q)|q.front().map(|f|(*p,aged(p,f))))
+                    .max_by_key(|(_,aged)|*aged)?;
+                p
+            }
+        };
+        let bucket = self.buckets.get_mut(&p)?;
+        let queued = bucket.pop_front()?;
+        if bucket.is_empty() {
+            self.buckets.remove(&p);
+        }
+        self.len -= 1;
+        Some(queued)
+    }
+
+    fn len(&self)->usize {
+        self.len
+    }
+
+    /// the highest priority of the tasks, aging not counted
+    fn top(&self)->Option<Priority> {
+        self.buckets.keys().next_back().copied()
+    }
+
+    fn clear(&mut self) {
+        self.buckets.clear();
+        self.len = 0;
+    }
+}
+
+/// The local deque of a worker in work-stealing mode.
+type LocalTasks = Arc<Mutex<Tasks>>;
+/// The local deques of all the workers serving a queue, from which idle workers steal.
+type Stealers = RwLock<Vec<LocalTasks>>;
+
+/// how long an idle worker waits before trying to steal again
+const STEAL_IDLE: Duration = Duration::from_millis(10);
+
+/// the slots of the lock-free ring of `Queue::lock_free()`
+const LOCK_FREE_CAPACITY: usize = 4096;
+
+/// The fast path of the shared deque of `Queue::lock_free()`.
+///
+/// Tasks of `Priority::NORMAL` go through the lock-free ring,
+/// the others, and the ones overflowing the ring, go into the locked `Tasks`.
+/// The normal ones keep going into `Tasks` until it is drained, so they are still run in order.
+struct LockFree {
+    ring: Ring<Queued>,
+    /// the count of the tasks in the locked `Tasks`
+    locked: AtomicUsize,
+    /// the count of the workers parked or about to park
+    sleepers: AtomicUsize,
+}
+
+thread_local! {
+    /// the queue served by the current worker, and its local deque in work-stealing mode
+    static WORKER_LOCAL: RefCell<Option<(usize,Option<LocalTasks>)>> = const { RefCell::new(None) };
+}
+
+/// The shared state of a `Queue`:
+/// the shared deque, signaled when a task is put in, the local deques of the workers,
+/// the lock-free fast path, the signal of room for the submitters blocked on a bounded queue,
+/// and the metrics.
+type Shared = (Mutex<Tasks>,Condvar,Stealers,Option<LockFree>,Condvar,QueueMeter);
+
 /// A queue holding tasks awaiting scheduling by threads
+///
+/// Tasks of higher [`Priority`] are popped first, see [`TaskNeed::priority()`](crate::TaskNeed::priority).
 #[derive(Clone)]
-pub struct Queue(Arc<(Mutex<VecDeque<(Box<TaskDo>,Box<PostDo>)>>,Condvar)>);
+pub struct Queue(Arc<Shared>);
 
 impl Queue {
     pub fn new()->Self {
-        Queue(Arc::new((Mutex::new(VecDeque::new()),Condvar::new())))
+        Self::with(Tasks::new(None))
+    }
+
+    /// Creates a queue in which a waiting task gains one priority level per `step`,
+    /// so low priority tasks are not starved by a flood of higher ones.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::Queue;
+    /// # use std::time::Duration;
+    /// // a task of Priority::LOW catches up with Priority::NORMAL after waiting 64ms
+    /// let queue = Queue::with_aging(Duration::from_millis(1));
+    /// ```
+    pub fn with_aging(step:Duration)->Self {
+        Self::with(Tasks::new(Some(step)))
+    }
+
+    /// Creates a queue whose shared deque is a lock-free ring,
+    /// so many threads submitting tasks or serving the queue do not contend on a lock.
+    ///
+    /// The idle threads are parked, and woken up only when they are parked.
+    /// Tasks of `Priority::NORMAL` take the lock-free path,
+    /// the others take the locked one, so the priorities are still honored;
+    /// so do the tasks overflowing the ring of 4096 slots.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool,Queue,TaskBuildNew};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::lock_free()).unwrap();
+    /// for _ in 0..4 {
+    ///     pool.spawn_thread_for(qid);
+    /// }
+    /// let producers: Vec<_> = (0..4).map(|_|{
+    ///     let submitter = pool.task_submitter(qid).unwrap();
+    ///     std::thread::spawn(move||for i in 0..100 {
+    ///         submitter.submit((move||i*2).into_task());
+    ///     })
+    /// }).collect();
+    /// producers.into_iter().for_each(|p|p.join().unwrap());
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// for _ in 0..4 {
+    ///     submitter.submit((||{}).into_exit_task());
+    /// }
+    /// pool.join();
+    /// ```
+    pub fn lock_free()->Self {
+        let fast = LockFree {
+            ring: Ring::new(LOCK_FREE_CAPACITY),
+            locked: AtomicUsize::new(0),
+            sleepers: AtomicUsize::new(0),
+        };
+        Queue(Arc::new((Mutex::new(Tasks::new(None)),Condvar::new(),RwLock::new(Vec::new()),Some(fast),Condvar::new(),QueueMeter::default())))
+    }
+
+    /// Creates a queue holding at most `capacity` tasks, to apply backpressure on the submitters.
+    ///
+    /// When it is full, [`submit()`](crate::TaskSubmitter::submit) blocks until there is room,
+    /// [`submit_timeout()`](crate::TaskSubmitter::submit_timeout) blocks for a while,
+    /// and [`try_submit()`](crate::TaskSubmitter::try_submit) returns `TaskSubmitError::QueueFull`.
+    ///
+    /// Only the tasks without conditions are limited when submitted,
+    /// the conditional ones wait in the waitQueue instead.
+    /// The tasks put into the queue by the pool itself are always admitted, even beyond the capacity,
+    /// for the threads doing so must not be blocked:
+    /// - conditional tasks released when their last condition arrives
+    /// - delayed, recurring, expired and fallback tasks put in by the timer
+    /// - tasks submitted by a thread serving the same queue, which would otherwise wait for itself
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool,Queue,TaskBuildNew,TaskSubmitError};
+    /// # use std::time::Duration;
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::bounded(1)).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// assert!(submitter.try_submit((||1).into_task()).is_ok());
+    /// assert!(matches!(submitter.try_submit((||2).into_task()), Err(TaskSubmitError::QueueFull(_))));
+    /// assert!(submitter.submit_timeout(Duration::from_millis(1), (||3).into_task()).is_err());
+    ///
+    /// pool.spawn_thread_for(qid);
+    /// // blocks until the first one is popped
+    /// submitter.submit((||{}).into_exit_task());
+    /// pool.join();
+    /// ```
+    pub fn bounded(capacity:usize)->Self {
+        let mut tasks = Tasks::new(None);
+        tasks.capacity = Some(capacity.max(1));
+        Self::with(tasks)
+    }
+
+    /// the capacity of a bounded queue, `None` if unbounded
+    pub fn capacity(&self)->Option<usize> {
+        self.0.0.lock().unwrap().capacity
+    }
+
+    fn with(tasks:Tasks)->Self {
+        Queue(Arc::new((Mutex::new(tasks),Condvar::new(),RwLock::new(Vec::new()),None,Condvar::new(),QueueMeter::default())))
+    }
+
+    /// Enables the work-stealing mode, must be set before threads are spawned for the queue.
+    ///
+    /// Each thread serving the queue has a local deque besides the shared one.
+    /// The conditional tasks released into this queue by a thread, when their last condition comes from it,
+    /// are put into its local deque, to be run next by the same thread without contending on the shared one.
+    /// An idle thread steals from the local deques of its siblings.
+    /// The priorities are honored within each deque.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool,Queue,TaskBuildNew};
+    /// let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::new().work_stealing()).unwrap();
+    /// let submitter = pool.task_submitter(qid).unwrap();
+    /// let sum = submitter.submit((|a:i32,b:i32|println!("sum={}",a+b)).into_exit_task()).take();
+    /// submitter.submit((||1).into_task().bind_to(sum.input_ca::<0>()));
+    /// submitter.submit((||2).into_task().bind_to(sum.input_ca::<1>()));
+    /// pool.spawn_thread_for(qid);
+    /// pool.join();
+    /// ```
+    pub fn work_stealing(self)->Self {
+        self.0.0.lock().unwrap().stealing = true;
+        self
+    }
+
+    /// identifies the queue, for the worker threads to know which queue they serve.
+    fn key(&self)->usize {
+        Arc::as_ptr(&self.0) as usize
     }
 
     pub(crate) fn add_boxtask(&self,taskcompiled:(Box<TaskDo>,Box<PostDo>)) {
+        self.0.5.enqueued();
+        self.put(taskcompiled);
+    }
+
+    /// puts the task into the shared deque, not counted as submitted.
+    fn put(&self,taskcompiled:(Box<TaskDo>,Box<PostDo>)) {
+        if let Some(fast) = &self.0.3 {
+            return self.add_lock_free(fast, taskcompiled);
+        }
         let mut lock = self.0.0.lock().unwrap();
-        let is_empty = lock.is_empty();
         lock.push_back(taskcompiled);
-        if is_empty {
+        // not only when it was empty, several threads may be waiting on the queue.
+        self.0.1.notify_one();
+    }
+
+    fn add_lock_free(&self, fast:&LockFree, (task,postdo):(Box<TaskDo>,Box<PostDo>)) {
+        let queued = Queued { at: Instant::now(), task, postdo };
+        let queued = if queued.task.priority() == Priority::NORMAL
+            && fast.locked.load(Ordering::Acquire) == 0 {
+            match fast.ring.push(queued) {
+                Ok(()) => {
+                    // pairs with the fence in `park()`, either the parking worker sees the task or it is seen parking.
+                    fence(Ordering::SeqCst);
+                    if fast.sleepers.load(Ordering::Relaxed) > 0 {
+                        let _lock = self.0.0.lock().unwrap();
+                        self.0.1.notify_one();
+                    }
+                    return;
+                }
+                Err(queued) => queued,
+            }
+        } else {
+            queued
+        };
+        let mut lock = self.0.0.lock().unwrap();
+        lock.push_queued(queued);
+        fast.locked.fetch_add(1, Ordering::AcqRel);
+        if fast.sleepers.load(Ordering::SeqCst) > 0 {
             self.0.1.notify_one();
         }
     }
 
+    /// pops from the lock-free ring, unless a task of higher priority is in the locked `Tasks`.
+    fn pop_lock_free(&self, fast:&LockFree)->Option<Queued> {
+        let take = |lock:&mut Tasks| {
+            let queued = lock.pop_queued()?;
+            fast.locked.fetch_sub(1, Ordering::AcqRel);
+            Some(queued)
+        };
+        if fast.locked.load(Ordering::Acquire) > 0 {
+            let mut lock = self.0.0.lock().unwrap();
+            if lock.top().is_some_and(|p|p > Priority::NORMAL) {
+                return take(&mut lock);
+            }
+        }
+        if let Some(queued) = fast.ring.pop() {
+            return Some(queued);
+        }
+        if fast.locked.load(Ordering::Acquire) > 0 {
+            return take(&mut self.0.0.lock().unwrap());
+        }
+        None
+    }
+
+    /// pops from the shared deque.
+    fn pop_shared(&self)->Option<Queued> {
+        match &self.0.3 {
+            Some(fast) => self.pop_lock_free(fast),
+            None => {
+                let mut lock = self.0.0.lock().unwrap();
+                let queued = lock.pop_queued()?;
+                if lock.capacity.is_some() {
+                    // room for a blocked submitter
+                    self.0.4.notify_one();
+                }
+                Some(queued)
+            }
+        }
+    }
+
+    /// waits for a task to be put into the queue, or the worker to be told to exit,
+    /// or `idle` to elapse if given.
+    /// the worker in work-stealing mode wakes up periodically to steal.
+    fn park(&self, stealing:bool, state:&WorkerState, idle:Option<Duration>) {
+        let m = self.0.0.lock().unwrap();
+        // checked under the lock, for `wake_all()` to be not missed.
+        if !state.is_running() {
+            return;
+        }
+        let fast = self.0.3.as_ref();
+        if let Some(fast) = fast {
+            fast.sleepers.fetch_add(1, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+        }
+        if m.is_empty() && fast.is_none_or(|f|f.ring.is_empty()) {
+            // a sibling pushes into its local deque without the shared lock, so the notification may be missed.
+            let timeout = if stealing { Some(idle.map_or(STEAL_IDLE, |d|d.min(STEAL_IDLE))) } else { idle };
+            match timeout {
+                Some(timeout) => {
+                    let _unused = self.0.1.wait_timeout(m, timeout);
+                }
+                None => {
+                    let _unused = self.0.1.wait(m);
+                }
+            }
+        }
+        if let Some(fast) = fast {
+            fast.sleepers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// puts the task into the queue, waiting for room for `timeout` if it is bounded and full,
+    /// forever if `timeout` is `None`. gives the task back if timed out.
+    ///
+    /// the task is admitted beyond the capacity if the current thread serves the queue,
+    /// which would otherwise wait for itself, or if it is the timer thread, which must not be blocked.
+    /// also gives the task back if `closed` is set while waiting.
+    pub(crate) fn add_within(&self,taskcompiled:(Box<TaskDo>,Box<PostDo>),timeout:Option<Duration>,closed:&AtomicBool)
+        ->Result<(),(Box<TaskDo>,Box<PostDo>)>
+    {
+        let mut lock = self.0.0.lock().unwrap();
+        let Some(capacity) = lock.capacity else {
+            drop(lock);
+            self.add_boxtask(taskcompiled);
+            return Ok(());
+        };
+        if lock.len() >= capacity && (self.is_served_by_current() || crate::timer::is_timer_thread()) {
+            debug!("the queue is full, the task submitted by its own thread or the timer is admitted beyond the capacity.");
+        } else {
+            let deadline = timeout.map(|t|Instant::now() + t);
+            while lock.len() >= capacity {
+                if closed.load(Ordering::Acquire) {
+                    return Err(taskcompiled);
+                }
+                lock = match deadline {
+                    None => self.0.4.wait(lock).unwrap(),
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            return Err(taskcompiled);
+                        }
+                        self.0.4.wait_timeout(lock, deadline - now).unwrap().0
+                    }
+                };
+            }
+        }
+        lock.push_back(taskcompiled);
+        self.0.5.enqueued();
+        self.0.1.notify_one();
+        Ok(())
+    }
+
+    /// wakes all the threads waiting on the queue, the workers to check whether to exit,
+    /// and the submitters blocked on a full queue to check whether the pool is closed.
+    pub(crate) fn wake_all(&self) {
+        let _lock = self.0.0.lock().unwrap();
+        self.0.1.notify_all();
+        self.0.4.notify_all();
+    }
+
+    /// whether the current thread is a worker serving this queue
+    fn is_served_by_current(&self)->bool {
+        let key = self.key();
+        WORKER_LOCAL.with(|w|w.borrow().as_ref().is_some_and(|(q,_)|*q == key))
+    }
+
+    /// puts a task released by a worker of this queue into its local deque in work-stealing mode,
+    /// otherwise the same as `add_boxtask()`.
+    pub(crate) fn add_ready(&self,taskcompiled:(Box<TaskDo>,Box<PostDo>)) {
+        let key = self.key();
+        let taskcompiled = WORKER_LOCAL.with(|w|match &*w.borrow() {
+            Some((q,Some(local))) if *q == key => {
+                local.lock().unwrap().push_back(taskcompiled);
+                self.0.5.enqueued();
+                None
+            }
+            _ => Some(taskcompiled),
+        });
+        match taskcompiled {
+            Some(taskcompiled) => self.add_boxtask(taskcompiled),
+            // wakes an idle sibling to steal
+            None => self.0.1.notify_one(),
+        }
+    }
+
+    /// records the current thread as a worker of the queue,
+    /// and registers its local deque if in work-stealing mode.
+    fn join_worker(&self)->Option<LocalTasks> {
+        let local = self.0.0.lock().unwrap().stealing.then(||{
+            let local = Arc::new(Mutex::new(Tasks::new(None)));
+            self.0.2.write().unwrap().push(local.clone());
+            local
+        });
+        WORKER_LOCAL.with(|w|*w.borrow_mut() = Some((self.key(),local.clone())));
+        local
+    }
+
+    /// unregisters the exiting worker, and moves the tasks left in its local deque into the shared one.
+    fn leave_worker(&self, local:Option<LocalTasks>) {
+        WORKER_LOCAL.with(|w|*w.borrow_mut() = None);
+        let Some(local) = local else {
+            return;
+        };
+        self.0.2.write().unwrap().retain(|l|!Arc::ptr_eq(l, &local));
+        let mut left = local.lock().unwrap();
+        while let Some(taskcompiled) = left.pop_front() {
+            self.put(taskcompiled);
+        }
+    }
+
+    /// pops a task from the local deque of a sibling worker.
+    fn steal(&self, local:&LocalTasks)->Option<Queued> {
+        let stealers = self.0.2.read().unwrap();
+        stealers
+            .iter()
+            .filter(|l|!Arc::ptr_eq(l, local))
+            .find_map(|l|l.lock().unwrap().pop_queued())
+    }
+
+    /// pops the next task for a worker, from its local deque, the shared one, or its siblings in order.
+    /// waits, at most for `idle` if given, and returns `None` if there is none.
+    fn next_task(&self, local:Option<&LocalTasks>, state:&WorkerState, idle:Option<Duration>)->Option<(Box<TaskDo>,Box<PostDo>)> {
+        let popped = |Queued { at, task, postdo }| {
+            self.0.5.dequeued(at);
+            Some((task,postdo))
+        };
+        if let Some(local) = local
+            && let Some(queued) = local.lock().unwrap().pop_queued() {
+            return popped(queued);
+        }
+        if let Some(queued) = self.pop_shared() {
+            return popped(queued);
+        }
+        if let Some(local) = local
+            && let Some(queued) = self.steal(local) {
+            trace!("a task is stolen from a sibling.");
+            return popped(queued);
+        }
+        self.park(local.is_some(), state, idle);
+        None
+    }
+
     #[allow(dead_code)]
     pub(crate) fn pop(&self)->Option<(Box<TaskDo>,Box<PostDo>)> {
-        self
-            .0
-            .0
-            .lock()
-            .unwrap()
-            .pop_front()
+        let Queued { at, task, postdo } = self.pop_shared()?;
+        self.0.5.dequeued(at);
+        Some((task,postdo))
     }
     
-    #[allow(dead_code)]
-    fn clear(&self) {
-        self
-            .0
-            .0
-            .lock()
-            .unwrap()
-            .clear()
+    /// whether both are the same queue, not a copy of its configuration.
+    pub(crate) fn same(&self, other:&Queue)->bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// drops all the tasks in the queue, including the ones in the lock-free ring.
+    pub(crate) fn clear(&self) {
+        let mut lock = self.0.0.lock().unwrap();
+        let mut cleared = lock.len();
+        lock.clear();
+        drop(lock);
+        self.0.4.notify_all();
+        if let Some(fast) = &self.0.3 {
+            fast.locked.store(0, Ordering::Release);
+            while fast.ring.pop().is_some() {
+                cleared += 1;
+            }
+        }
+        self.0.5.removed(cleared);
     }
 
-    /// get the length of the queue
+    /// get the length of the queue, including the local deques in work-stealing mode
     pub fn len(&self)->usize {
-        self
+        let len = self
             .0
             .0
             .lock()
             .unwrap()
-            .len()
+            .len();
+        let len = len + self.0.3.as_ref().map_or(0, |f|f.ring.len());
+        let stealers = self.0.2.read().unwrap();
+        len + stealers.iter().map(|l|l.lock().unwrap().len()).sum::<usize>()
+    }
+
+    /// the metrics of the queue, see [`Pool::stats()`](crate::Pool::stats) for those of a whole pool.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::Queue;
+    /// let queue = Queue::new();
+    /// let stats = queue.stats();
+    /// assert_eq!((stats.submitted(), stats.executed(), stats.len()), (0, 0, 0));
+    /// ```
+    pub fn stats(&self)->QueueStats {
+        self.meter().snapshot(0, self.len())
+    }
+
+    pub(crate) fn meter(&self)->&QueueMeter {
+        &self.0.5
+    }
+}
+
+/// The kind of a queue created by a [`Scope`](crate::Scope), see [`Scope::new_queue()`](crate::Scope::new_queue),
+/// the same as the constructors of [`Queue`].
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{scope, QueueConfig};
+/// # use std::time::Duration;
+/// scope(|s|{
+///     let qid = s.new_queue(QueueConfig::new().bounded(64).aging(Duration::from_millis(1))).unwrap();
+///     s.spawn_thread_for(qid);
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueueConfig {
+    aging: Option<Duration>,
+    capacity: Option<usize>,
+    lock_free: bool,
+    work_stealing: bool,
+}
+
+impl QueueConfig {
+    pub fn new()->Self {
+        Self::default()
+    }
+
+    /// a waiting task gains one priority level per `step`, see [`Queue::with_aging()`].
+    pub fn aging(mut self, step:Duration)->Self {
+        self.aging = Some(step);
+        self
+    }
+
+    /// holds at most `capacity` tasks, see [`Queue::bounded()`].
+    pub fn bounded(mut self, capacity:usize)->Self {
+        self.capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// the shared deque is a lock-free ring, see [`Queue::lock_free()`];
+    /// the aging and the capacity are then ignored.
+    pub fn lock_free(mut self)->Self {
+        self.lock_free = true;
+        self
+    }
+
+    /// see [`Queue::work_stealing()`].
+    pub fn work_stealing(mut self)->Self {
+        self.work_stealing = true;
+        self
+    }
+
+    pub(crate) fn build(&self)->Queue {
+        let queue = if self.lock_free {
+            Queue::lock_free()
+        } else {
+            let mut tasks = Tasks::new(self.aging);
+            tasks.capacity = self.capacity;
+            Queue::with(tasks)
+        };
+        if self.work_stealing { queue.work_stealing() } else { queue }
     }
 }
 
@@ -72,49 +655,476 @@ impl Queue {
 /// - #1: queue: &Queue
 /// Returns:
 /// - return Jhandle
+///
+/// A panic raised by a task is caught and logged, and the thread keeps running.
+/// Use [`Pool::spawn_thread_for()`](crate::Pool::spawn_thread_for) to have panics reported to the pool.
 pub fn spawn_thread(queue:&Queue)-> Jhandle {
-    let quit_flag = Arc::<AtomicBool>::new(AtomicBool::new(false));
-    let quit = quit_flag.clone();
-    let queue = queue.0.clone();
-    let handle = thread::spawn(move||{
+    spawn_worker(queue, WorkerCtx::default(), thread::Builder::new())
+        .expect("failed to spawn thread")
+}
+
+/// Tells a worker thread to keep running, to exit once the queue is drained, or after the current task.
+pub(crate) struct WorkerState(AtomicU8);
+
+impl WorkerState {
+    const RUNNING: u8 = 0;
+    const DRAINING: u8 = 1;
+    const STOPPING: u8 = 2;
+
+    fn new()->Self {
+        Self(AtomicU8::new(Self::RUNNING))
+    }
+    /// exits once no task is found, unless it has been told to stop.
+    pub(crate) fn drain(&self) {
+        let _ = self.0.compare_exchange(Self::RUNNING, Self::DRAINING, Ordering::AcqRel, Ordering::Acquire);
+    }
+    pub(crate) fn stop(&self) {
+        self.0.store(Self::STOPPING, Ordering::Release);
+    }
+    fn is_running(&self)->bool {
+        self.0.load(Ordering::Acquire) == Self::RUNNING
+    }
+    fn is_draining(&self)->bool {
+        self.0.load(Ordering::Acquire) == Self::DRAINING
+    }
+    fn is_stopping(&self)->bool {
+        self.0.load(Ordering::Acquire) == Self::STOPPING
+    }
+}
+
+/// The pool-wide context of a worker thread.
+#[derive(Clone, Default)]
+pub(crate) struct WorkerCtx {
+    /// 0 if the thread is not spawned by a `Pool`
+    pub(crate) qid: usize,
+    pub(crate) panics: PanicReport,
+    pub(crate) on_start: Option<Arc<WorkerHook>>,
+    pub(crate) on_stop: Option<Arc<WorkerHook>>,
+    /// the autoscaler the thread is spawned by, to retire once idle
+    pub(crate) scaler: Option<Arc<Scaler>>,
+    /// the live workers of the pool, for their metrics
+    pub(crate) workers: Workers,
+    pub(crate) observers: Observers,
+}
+
+pub(crate) type WorkerHook = dyn Fn() + Send + Sync;
+
+/// The count of the threads spawned for each queue of a pool, to number their names.
+#[derive(Clone, Default)]
+pub(crate) struct Spawned(Arc<Mutex<HashMap<usize,usize>>>);
+
+impl Spawned {
+    /// the number of the next thread spawned for the queue
+    pub(crate) fn next(&self, qid:usize)->usize {
+        let mut spawned = self.0.lock().unwrap();
+        let n = spawned.entry(qid).or_default();
+        *n += 1;
+        *n - 1
+    }
+}
+
+/// How the threads serving a queue are spawned, see [`Pool::spawn_threads_for()`](crate::Pool::spawn_threads_for).
+///
+/// By default one thread is spawned, with the default stack size,
+/// and named `taskorch-q{qid}-w{n}` where `n` counts the threads spawned for the queue.
+/// The name shows in debuggers, panic messages and log lines.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{Pool, Queue, WorkerConfig};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let config = WorkerConfig::new()
+///     .name("io")
+///     .stack_size(256 * 1024)
+///     .count(2)
+///     .on_start(||println!("{:?} starts", std::thread::current().name()));
+/// let tids = pool.spawn_threads_for(qid, config).unwrap();
+/// assert_eq!(tids.len(), 2);
+/// ```
+#[derive(Clone)]
+pub struct WorkerConfig {
+    name: Option<String>,
+    stack_size: Option<usize>,
+    count: usize,
+    on_start: Option<Arc<WorkerHook>>,
+    on_stop: Option<Arc<WorkerHook>>,
+}
+
+impl WorkerConfig {
+    pub fn new()->Self {
+        Self { name: None, stack_size: None, count: 1, on_start: None, on_stop: None }
+    }
+
+    /// the prefix of the thread names, the threads are named `{prefix}-w{n}`.
+    pub fn name(mut self, prefix:impl Into<String>)->Self {
+        self.name = Some(prefix.into());
+        self
+    }
+
+    /// the stack size of each thread in bytes.
+    pub fn stack_size(mut self, bytes:usize)->Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// the count of the threads to spawn.
+    pub fn count(mut self, n:usize)->Self {
+        self.count = n;
+        self
+    }
+
+    /// called in each thread before it serves the queue, e.g. to set up thread-locals.
+    pub fn on_start<F>(mut self, f:F)->Self
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.on_start = Some(Arc::new(f));
+        self
+    }
+
+    /// called in each thread once it has stopped serving the queue.
+    pub fn on_stop<F>(mut self, f:F)->Self
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.on_stop = Some(Arc::new(f));
+        self
+    }
+
+    pub(crate) fn get_count(&self)->usize {
+        self.count
+    }
+
+    /// the builder of the `n`-th thread serving the queue `qid`, with the hooks put in the ctx.
+    pub(crate) fn builder(&self, qid:usize, n:usize, ctx:&mut WorkerCtx)->thread::Builder {
+        ctx.on_start = self.on_start.clone();
+        ctx.on_stop = self.on_stop.clone();
+        let name = match &self.name {
+            Some(prefix) => format!("{prefix}-w{n}"),
+            None => format!("taskorch-q{qid}-w{n}"),
+        };
+        let builder = thread::Builder::new().name(name);
+        match self.stack_size {
+            Some(bytes) => builder.stack_size(bytes),
+            None => builder,
+        }
+    }
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for WorkerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"WorkerConfig{{name:{:?},stack_size:{:?},count:{}}}",self.name,self.stack_size,self.count)
+    }
+}
+
+pub(crate) fn spawn_worker(queue:&Queue, ctx:WorkerCtx, builder:thread::Builder)-> io::Result<Jhandle> {
+    let state = Arc::new(WorkerState::new());
+    let worker = state.clone();
+    let queue = queue.clone();
+    let q = queue.clone();
+    let handle = builder.spawn(move||{
+        worker_span!(ctx.qid);
         warn!("thread starts ok.");
+        if let Some(on_start) = &ctx.on_start {
+            on_start();
+        }
+        let local = queue.join_worker();
+        let meter = ctx.workers.join(ctx.qid);
+        let keep_alive = ctx.scaler.as_ref().map(|s|s.keep_alive());
+        let mut busy_at = Instant::now();
+        let mut retired = false;
         loop {
-            if quit.load(Ordering::Relaxed) {
+            if worker.is_stopping() {
                 warn!("Quit flag detected and prepare to exit.");
                 break;
             }
-            
-            let mut m = queue.0.lock().unwrap();
-            if let Some((task,postdo)) = m.pop_front() {
-                drop(m);
-                debug!("task#{:?} is scheduled to run.",task.id());
+
+            // read before looking for a task: a worker woken by a push just before the shutdown
+            // must not take the missed task for a drained queue.
+            let draining = worker.is_draining();
+            if let Some((task,postdo)) = queue.next_task(local.as_ref(), &worker, keep_alive) {
+                let taskid = task.id();
+                task_span!(taskid, ctx.qid);
+                debug!("task#{:?} is scheduled to run.",taskid);
                 let kind = task.kind();
-                let r = task.run();
-                // if let Some(r) = r {
+                // the postdo also runs user code (e.g. `map_tuple_with`), so it is isolated as well.
+                ctx.observers.notify(|o|o.started(taskid, ctx.qid));
+                let started = Instant::now();
+                let mut retried = false;
+                let ok = run_isolated(||{
+                    let r = task.run();
+                    retried = r.is::<Retried>();
                     postdo(r);
-                // }
+                }, taskid, ctx.qid, &ctx.panics, &ctx.observers);
+                if retried {
+                    // a failed attempt, the task is not finished until its last one
+                    busy_at = Instant::now();
+                    continue;
+                }
+                let run = started.elapsed();
+                queue.meter().ran(run, ok);
+                meter.ran(run);
+                if ok {
+                    ctx.observers.notify(|o|o.finished(taskid, ctx.qid, run));
+                }
                 if let Kind::Exit = kind {
                     warn!("current thread received an exit message and prepare to exit.");
                     break;
                 }
-            } else {
-                let _unused = queue.1.wait(m);
+                busy_at = Instant::now();
+            } else if draining {
+                warn!("the queue is drained and prepare to exit.");
+                break;
+            } else if let Some(scaler) = &ctx.scaler
+                && busy_at.elapsed() >= scaler.keep_alive()
+                && scaler.retire() {
+                info!("idle for the keep-alive period and retired.");
+                retired = true;
+                break;
             }
         }
+        queue.leave_worker(local);
+        ctx.workers.leave(&meter);
+        if !retired && let Some(scaler) = &ctx.scaler {
+            scaler.left();
+        }
+        if let Some(on_stop) = &ctx.on_stop {
+            on_stop();
+        }
         info!("current thread exited normally.");
-    });
-    Jhandle(handle,quit_flag)
+    })?;
+    Ok(Jhandle(handle,state,q))
+}
+
+/// The result of canceling a conditional task, see [`TaskSubmitter::cancel()`](crate::TaskSubmitter::cancel).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Cancellation {
+    /// The task was waiting for its conditions, and has been removed with its received conditions dropped.
+    Canceled,
+    /// All conditions of the task have arrived, it has been put into its queue and can not be canceled.
+    AlreadyQueued,
+    /// The task has been run (or was dropped after being queued).
+    AlreadyRun,
+    /// No such task is waiting, or it is too old to be remembered.
+    NotFound,
+}
+
+#[derive(Clone, Copy)]
+enum Released {
+    Queued,
+    Run,
+}
+
+/// The count of the latest released tasks whose states are remembered for `cancel()`.
+const RELEASED_HISTORY: usize = 1024;
+
+/// The states of the latest released conditional tasks, the oldest are forgotten first.
+#[derive(Default)]
+struct ReleasedHistory {
+    states: HashMap<NonZeroUsize,Released>,
+    order: VecDeque<NonZeroUsize>,
+}
+
+impl ReleasedHistory {
+    fn queued(&mut self, taskid:NonZeroUsize) {
+        if self.states.insert(taskid, Released::Queued).is_none() {
+            self.order.push_back(taskid);
+        }
+        while self.order.len() > RELEASED_HISTORY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.states.remove(&oldest);
+            }
+        }
+    }
+    fn run(&mut self, taskid:NonZeroUsize) {
+        if let Some(state @ Released::Queued) = self.states.get_mut(&taskid) {
+            *state = Released::Run;
+        }
+    }
+}
+
+/// Records the released task as run when dropped, either after the task completed or panicked.
+pub(crate) struct RunGuard(pub(crate) TaskId,pub(crate) C1map);
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        if let TaskId(Some(taskid)) = self.0 {
+            self.1.mark_run(taskid);
+        }
+    }
+}
+
+/// A conditional task waiting in the waitQueue for its conditions.
+pub(crate) struct Waiting {
+    pub(crate) task: Box<TaskDo>,
+    pub(crate) postdo: Box<PostDo>,
+    /// the deadline and the policy applied when it is reached
+    pub(crate) expiry: Option<(Instant,ExpirePolicy)>,
+    /// the task is held in the waitQueue until then, even if all conditions have arrived
+    pub(crate) not_before: Option<Instant>,
+    /// the queue the task is put into when released, the one of the last producer if `None`
+    pub(crate) home: Option<Home>,
+    /// when the first condition arrived
+    pub(crate) arrived: Option<Instant>,
+}
+
+/// A queue with its id for logs.
+pub(crate) type Home = (usize,Queue);
+
+impl From<(Box<TaskDo>,Box<PostDo>)> for Waiting {
+    fn from((task,postdo): (Box<TaskDo>,Box<PostDo>)) -> Self {
+        Self { task, postdo, expiry: None, not_before: None, home: None, arrived: None }
+    }
 }
 
 #[derive(Clone)]
-pub(crate) struct C1map(Arc<(Mutex<HashMap<NonZeroUsize,(Box<TaskDo>,Box<PostDo>)>>,Condvar)>);
+pub(crate) struct C1map(Arc<(Mutex<HashMap<NonZeroUsize,Waiting>>,Condvar,Mutex<ReleasedHistory>,ExpireReport,Producers,Recorder,Observers)>);
 
 impl C1map {
     pub(crate) fn new()->Self {
         Self(
-            Arc::new((Mutex::new(HashMap::new()),Condvar::new()))
+            Arc::new((Mutex::new(HashMap::new()),Condvar::new(),Mutex::new(ReleasedHistory::default()),ExpireReport::default(),Producers::default(),Recorder::default(),Observers::default()))
         )
     }
+
+    /// sets the handler called when a task expired.
+    pub(crate) fn on_expire(&self, handler:Box<Handler<TaskExpired>>) {
+        self.0.3.set(handler);
+    }
+
+    /// Applies the expire policy to the task, if it is still waiting and its deadline is reached.
+    /// The fallback or the defaulted task is put into its home queue, or `q` if it has none.
+    pub(crate) fn expire(&self, taskid:NonZeroUsize, (qid,q):(usize,Queue)) {
+        let mut lock = self.0.0.lock().unwrap();
+        // it has been released or canceled, or replaced by one without deadline.
+        let Some(Waiting{expiry:Some((deadline,_)),..}) = lock.get(&taskid) else {
+            return;
+        };
+        // replaced by one with a later deadline
+        if *deadline > Instant::now() {
+            return;
+        }
+        let Some(Waiting{mut task,postdo,expiry:Some((_,policy)),not_before,home,arrived}) = lock.remove(&taskid) else {
+            unreachable!("the task has been checked to be waiting with a deadline");
+        };
+        let (qid,q) = home.clone().unwrap_or((qid,q));
+        let missing: Vec<u8> = task.as_param_mut()
+            .map(|p|(0..p.arity()).filter(|&i|!p.is_set(i)).map(|i|i as u8).collect())
+            .unwrap_or_default();
+        let tid = TaskId(Some(taskid));
+        let action = match policy {
+            ExpirePolicy::Drop => {
+                drop(lock);
+                drop((task,postdo));
+                ExpireAction::Dropped
+            }
+            ExpirePolicy::Fallback(fallback) => {
+                drop(lock);
+                drop((task,postdo));
+                let fallback = (move||fallback(tid)).into_task();
+                q.add_boxtask((Box::new(fallback.task),Box::new(|_|{})));
+                ExpireAction::Fallback
+            }
+            ExpirePolicy::Defaults(values) => {
+                let filled = task.as_param_mut().is_some_and(|p|p.fill(&*values));
+                if filled && not_before.is_some_and(|t|t > Instant::now()) {
+                    // held until due, and released by the timer then.
+                    lock.insert(taskid, Waiting{task,postdo,expiry:None,not_before,home,arrived});
+                    ExpireAction::Defaulted
+                } else if filled {
+                    self.0.2.lock().unwrap().queued(taskid);
+                    drop(lock);
+                    q.add_boxtask((task,postdo));
+                    self.0.6.notify(|o|o.released(tid, qid));
+                    ExpireAction::Defaulted
+                } else {
+                    drop(lock);
+                    error!("cond-task#{tid:?} failed to fill the missing conditions with defaults, and is dropped.");
+                    drop((task,postdo));
+                    ExpireAction::Dropped
+                }
+            }
+        };
+        warn!("cond-task#{tid:?} expired with cond#{missing:?} missing, {action:?}.");
+        self.0.3.report(TaskExpired::new(tid, qid, missing, action));
+    }
+
+    /// removes the waiting task, dropping the conditions it has received.
+    pub(crate) fn cancel(&self, tid:TaskId)->Cancellation {
+        let TaskId(Some(ref taskid)) = tid else {
+            return Cancellation::NotFound;
+        };
+        let mut lock = self.0.0.lock().unwrap();
+        if let Some(task) = lock.remove(taskid) {
+            drop(lock);
+            // dropped out of the lock, for the conditions may have their own drop.
+            drop(task);
+            return Cancellation::Canceled;
+        }
+        // keep the map locked, so the task can not be released in the meantime.
+        let history = self.0.2.lock().unwrap();
+        match history.states.get(taskid) {
+            Some(Released::Queued) => Cancellation::AlreadyQueued,
+            Some(Released::Run) => Cancellation::AlreadyRun,
+            None => Cancellation::NotFound,
+        }
+    }
+
+    /// the bindings of the tasks not run yet
+    pub(crate) fn producers(&self)->&Producers {
+        &self.0.4
+    }
+
+    pub(crate) fn observers(&self)->&Observers {
+        &self.0.6
+    }
+
+    /// the waiting tasks with their inputs, and the bindings of the tasks not run yet.
+    /// a snapshot, the tasks running meanwhile may deliver their results just after.
+    pub(crate) fn graph(&self)->Graph {
+        let mut lock = self.0.0.lock().unwrap();
+        let mut graph = Graph::new(&self.0.4);
+        for (taskid,waiting) in lock.iter_mut() {
+            graph.add_node(*taskid, inputs(&mut *waiting.task));
+        }
+        graph
+    }
+
+    /// the count of the waiting tasks, and how long the released ones waited from their first condition.
+    pub(crate) fn stats(&self)->(usize,Histogram) {
+        (self.0.0.lock().unwrap().len(),self.0.5.snapshot())
+    }
+
+    /// the tasks not run yet, with the latest released ones.
+    pub(crate) fn export(&self)->GraphExport {
+        let mut lock = self.0.0.lock().unwrap();
+        let history = self.0.2.lock().unwrap();
+        let mut export = GraphExport::new(self.0.4.snapshot());
+        for (taskid,waiting) in lock.iter_mut() {
+            export.waiting(*taskid, waiting.task.kind(), waiting.home.as_ref().map(|home|home.0), inputs(&mut *waiting.task));
+        }
+        for taskid in &history.order {
+            export.released(*taskid);
+        }
+        export
+    }
+
+    /// removes all the waiting tasks, returns the count of them.
+    pub(crate) fn clear(&self)->usize {
+        let waiting = std::mem::take(&mut *self.0.0.lock().unwrap());
+        // dropped out of the lock, as `cancel()`.
+        waiting.len()
+    }
+
+    /// records that the released task has been run or dropped.
+    pub(crate) fn mark_run(&self, taskid:NonZeroUsize) {
+        self.0.2.lock().unwrap().run(taskid);
+    }
     pub(crate) fn check(&self, tid:TaskId)->Option<TaskId> {
         let TaskId(Some(ref taskid)) = tid else {
             return None;
@@ -128,7 +1138,7 @@ impl C1map {
         }
     }
 
-    pub(crate) fn insert(&self,taskcompiled:(Box<TaskDo>,Box<PostDo>),taskid:NonZeroUsize)->Inserted
+    pub(crate) fn insert(&self,taskcompiled:Waiting,taskid:NonZeroUsize)->Inserted
     {
         let mut lock = self.0.0.lock().unwrap();
         if let None = lock.insert(taskid, taskcompiled) {
@@ -138,7 +1148,7 @@ impl C1map {
         }
     }
 
-    pub(crate) fn try_insert(&self,taskcompiled:(Box<TaskDo>,Box<PostDo>),taskid:NonZeroUsize)->Option<NonZeroUsize>
+    pub(crate) fn try_insert(&self,taskcompiled:Waiting,taskid:NonZeroUsize)->Option<NonZeroUsize>
     {
         let mut lock = self.0.0.lock().unwrap();
         use std::collections::hash_map::Entry::{Occupied,Vacant};
@@ -154,12 +1164,30 @@ impl C1map {
     }
 
 
+    /// inserts all the tasks, or none if any of their ids is already waiting,
+    /// returns the ids conflicted then.
+    pub(crate) fn insert_all(&self,tasks:Vec<(NonZeroUsize,Waiting)>)->Result<(),Vec<TaskId>> {
+        let mut lock = self.0.0.lock().unwrap();
+        let conflicts: Vec<_> = tasks
+            .iter()
+            .filter(|(taskid,_)|lock.contains_key(taskid))
+            .map(|(taskid,_)|TaskId(Some(*taskid)))
+            .collect();
+        if !conflicts.is_empty() {
+            drop(lock);
+            // the tasks are dropped out of the lock, as `cancel()`.
+            return Err(conflicts);
+        }
+        lock.extend(tasks);
+        Ok(())
+    }
+
     pub(crate) fn _insert_old<T>(&self,task: T,postdo:Box<PostDo>,taskid:NonZeroUsize)->NonZeroUsize
     where T: Task + Send + 'static
     {
         let task: Box::<dyn Task + Send + 'static> = Box::new(task);
         let mut lock = self.0.0.lock().unwrap();
-        lock.insert(taskid, (task,postdo));
+        lock.insert(taskid, (task,postdo).into());
         taskid
     }
 
@@ -174,14 +1202,58 @@ impl C1map {
                 => None,
             Vacant(vacant_entry)
                 => {
-                vacant_entry.insert((task,postdo));
+                vacant_entry.insert((task,postdo).into());
                 Some(taskid)
             },
         }
     }
+    #[allow(dead_code)]
     fn remove(&self,id:&NonZeroUsize)->Option<(Box<dyn Task+Send>,Box<PostDo>)> {
         let mut lock = self.0.0.lock().unwrap();
-        lock.remove(id)
+        lock.remove(id).map(|w|(w.task,w.postdo))
+    }
+
+    /// removes the task if all its conditions are satisfied and it is due, and records it as queued.
+    fn release(&self,id:&NonZeroUsize)->Option<Waiting> {
+        let mut lock = self.0.0.lock().unwrap();
+        let waiting = lock.get_mut(id)?;
+        if waiting.not_before.is_some_and(|t|t > Instant::now()) {
+            debug!("cond task#{id:?} is held until due.");
+            return None;
+        }
+        if !waiting.task.as_param_mut().is_none_or(|p|p.is_full()) {
+            return None;
+        }
+        let waiting = lock.remove(id)?;
+        self.0.2.lock().unwrap().queued(*id);
+        if let Some(arrived) = waiting.arrived {
+            self.0.5.record(arrived.elapsed());
+        }
+        Some(waiting)
+    }
+
+    /// called by the timer when the task held by `not_before` is due,
+    /// it is put into its home queue, or `q` if it has none, if all its conditions have arrived,
+    /// otherwise when the last one arrives.
+    pub(crate) fn release_due(&self, taskid:NonZeroUsize, (qid,q):(usize,Queue)) {
+        {
+            let mut lock = self.0.0.lock().unwrap();
+            let Some(waiting) = lock.get_mut(&taskid) else {
+                return;
+            };
+            // replaced by one due later
+            if waiting.not_before.is_some_and(|t|t > Instant::now()) {
+                return;
+            }
+            waiting.not_before = None;
+        }
+        let Some(Waiting{task,postdo,home,..}) = self.release(&taskid) else {
+            return;
+        };
+        let (qid,q) = home.unwrap_or((qid,q));
+        debug!("cond task#{taskid:?} is due and scheduled to Q#{qid}");
+        q.add_boxtask((task,postdo));
+        self.0.6.notify(|o|o.released(TaskId(Some(taskid)), qid));
     }
 
     // Some(true): full
@@ -194,23 +1266,34 @@ impl C1map {
             trace!("The target value is () from task#{v_from:?} and will be ignored!.");
             return None;
         }
+        let r = self.set_ci(target_ca,(v,v_from));
+        // notified out of the lock, for an observer may submit tasks.
+        let delivery = Delivery::of(target_ca, *v_from);
+        match r {
+            Ok(_) => self.0.6.notify(|o|o.delivered(&delivery)),
+            Err(e) => self.0.6.notify(|o|o.delivery_failed(&delivery, e)),
+        }
+        r.ok()
+    }
+
+    fn set_ci<T:'static+Debug>(&self,target_ca:&CondAddr<T>,(v,v_from):(&T,&TaskId))->Result<bool,DeliveryError> {
         let TaskId(Some(ref target_taskid)) = target_ca.taskid() else {
             error!("target task#{:?} is ZERO, not avaiable! from task#{:?} {:?}", target_ca.taskid(), v_from,v);
-            return None;
+            return Err(DeliveryError::NoTask);
         };
         // @A : return None if target direction is not input
         let Section::Input = target_ca.section() else {
             error!("target task#{:?} the direction is not input {:?} from task#{:?}.", target_ca.taskid(), target_ca.argidx(), v_from);
-            return None;
+            return Err(DeliveryError::NotInput);
         };
         let mut lock = self.0.0.lock().unwrap();
-        let Some((target_task,_target_postdo)) = lock.get_mut(target_taskid) else {
+        let Some(Waiting{task:target_task,arrived,..}) = lock.get_mut(target_taskid) else {
             error!("target task#{:?} was not found, the cond#{:?} could not be updated from task#{:?}", target_ca.taskid(), target_ca.argidx(), v_from);
-            return None;
+            return Err(DeliveryError::NotWaiting);
         };
         let Some(param) = target_task.as_param_mut() else {
             error!("target task#{:?} failed to acquire cond#{:?}, update skipped from task#{:?}.", target_ca.taskid(), target_ca.argidx(), v_from);
-            return None;
+            return Err(DeliveryError::NoConditions);
         };
         if !param.set(target_ca.argidx().i() as usize, v) {
             // the _target_i must be type of Input, because processed at @A 
@@ -220,20 +1303,24 @@ impl C1map {
             let _data_type_name  = type_name::<T>();
             error!("target task#{_target_taskid:?}.cond#{_target_i:?} has type <{_target_type_name}> not identical to <{_data_type_name}>, \
                     cannot be updated with from task#{v_from:?}.{{{v:?}}}.");
-            return None;
+            if _target_i.i() as usize >= param.arity() {
+                return Err(DeliveryError::NoSuchInput);
+            }
+            return Err(DeliveryError::TypeMismatch { expected: param.typename(_target_i.i() as usize) });
         }
+        arrived.get_or_insert_with(Instant::now);
         if cfg!(feature="log-trace") {
             trace!("target task{{{target_ca:?}}} received from task{{{v_from:?}}}={{{v:?}}}");
         } else {
             debug!("target task{{{target_ca:?}}} received from task{{{v_from:?}}}");
         }
         let _ = v_from; // just ignore the warning
-        Some(param.is_full())
+        Ok(param.is_full())
     }
 }
 
-// tid and qid just used for log
-// #[allow(unused_variables)]
+// qid just used for log
+// the released task is put into its home queue, or the producer's `q` if it has none.
 pub(crate) fn when_ci_comed<T:'static+Debug>(target_ca:&CondAddr<T>, (v,v_from):(&T,&TaskId), c1map:C1map, (qid,q):(usize,Queue))->bool {
     let Some(true) = c1map.update_ci(target_ca,(v,v_from)) else {
         // the log has been processed in update_ci
@@ -244,12 +1331,14 @@ pub(crate) fn when_ci_comed<T:'static+Debug>(target_ca:&CondAddr<T>, (v,v_from):
         unreachable!("the taskid has checked in update_ci()!");
         // return false;
     };
-    let Some((target_task,postdo)) = c1map.remove(target_taskid) else {
-        error!("cond task#{:?} does not find.",target_ca.taskid());
+    let Some(Waiting{task,postdo,home,..}) = c1map.release(target_taskid) else {
+        debug!("cond task#{:?} is not released, maybe held until due, canceled or released by the timer.",target_ca.taskid());
         return  false;
     };
+    let (qid,q) = home.unwrap_or((qid,q));
     debug!("cond task#{:?} has all conditions been satified and scheduled to Q#{qid}", target_ca.taskid());
-    q.add_boxtask((target_task,postdo));
+    q.add_ready((task,postdo));
+    c1map.observers().notify(|o|o.released(target_ca.taskid(), qid));
     let _ = qid; // just for ignoring warning
     true
 }
@@ -327,4 +1416,115 @@ fn test_when_tuple_comed() {
 
     ().foreach(&id_from, c1map.clone(), (0,q.clone()));
     (&(42,43), &(cond_addr1,cond_addr2)).foreach(&id_from, c1map.clone(), (0,q.clone()));
-}
\ No newline at end of file
+}
+#[test]
+fn test_priority() {
+    let compiled = |n:i32,p:Priority| {
+        let task = (move||n).into_task().priority(p).task;
+        (Box::new(task) as Box<TaskDo>, Box::new(|_|{}) as Box<PostDo>)
+    };
+    let pop_all = |q:&Queue| {
+        std::iter::from_fn(||q.pop())
+            .map(|(task,_)|*task.run().downcast::<i32>().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let q = Queue::new();
+    for (n,p) in [(1,Priority::LOW),(2,Priority::NORMAL),(3,Priority::HIGH),(4,Priority::NORMAL),(5,Priority::LOW)] {
+        q.add_boxtask(compiled(n,p));
+    }
+    assert_eq!(q.len(), 5);
+    assert_eq!(pop_all(&q), vec![3,2,4,1,5]);
+
+    // the low one has waited long enough to catch up with the normal ones
+    let q = Queue::with_aging(Duration::from_millis(1));
+    q.add_boxtask(compiled(1,Priority::LOW));
+    thread::sleep(Duration::from_millis(80));
+    q.add_boxtask(compiled(2,Priority::NORMAL));
+    q.add_boxtask(compiled(3,Priority::HIGHEST));
+    assert_eq!(pop_all(&q), vec![3,1,2]);
+}
+
+#[test]
+fn test_work_stealing() {
+    use std::{collections::HashSet, sync::mpsc};
+    let compiled = |f:Box<dyn FnOnce()+Send>,kind:Kind| {
+        let mut task = f.into_task();
+        task.task.kind = kind;
+        (Box::new(task.task) as Box<TaskDo>, Box::new(|_|{}) as Box<PostDo>)
+    };
+    let q = Queue::new().work_stealing();
+    let workers = [spawn_thread(&q),spawn_thread(&q)];
+    let (tx,rx) = mpsc::channel();
+
+    // released in a worker, so put into its local deque, and stolen by the idle sibling
+    let q2 = q.clone();
+    q.add_boxtask(compiled(Box::new(move||{
+        for _ in 0..8 {
+            let tx = tx.clone();
+            q2.add_ready(compiled(Box::new(move||{
+                thread::sleep(Duration::from_millis(10));
+                tx.send(thread::current().id()).unwrap();
+            }),Kind::Normal));
+        }
+    }),Kind::Normal));
+    let threads: HashSet<_> = (0..8).map(|_|rx.recv().unwrap()).collect();
+    assert_eq!(threads.len(), 2);
+    assert_eq!(q.len(), 0);
+
+    // not in a worker, put into the shared deque
+    q.add_ready(compiled(Box::new(||{}),Kind::Exit));
+    q.add_ready(compiled(Box::new(||{}),Kind::Exit));
+    for w in workers {
+        w.join().unwrap();
+    }
+    assert!(q.0.2.read().unwrap().is_empty());
+}
+
+#[test]
+fn test_lock_free() {
+    use std::sync::mpsc;
+    let compiled = |n:i32,p:Priority| {
+        let task = (move||n).into_task().priority(p).task;
+        (Box::new(task) as Box<TaskDo>, Box::new(|_|{}) as Box<PostDo>)
+    };
+    let pop_all = |q:&Queue| {
+        std::iter::from_fn(||q.pop())
+            .map(|(task,_)|*task.run().downcast::<i32>().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // priorities honored, and in order after overflowing the ring
+    let q = Queue::lock_free();
+    let n = LOCK_FREE_CAPACITY as i32 + 10;
+    for i in 0..n {
+        q.add_boxtask(compiled(i,Priority::NORMAL));
+    }
+    q.add_boxtask(compiled(-1,Priority::LOW));
+    q.add_boxtask(compiled(-2,Priority::HIGH));
+    assert_eq!(q.len(), n as usize + 2);
+    let order = pop_all(&q);
+    assert_eq!(order[0], -2);
+    assert_eq!(order[1..=n as usize], (0..n).collect::<Vec<_>>());
+    assert_eq!(order[n as usize + 1], -1);
+
+    // parked workers are woken up
+    let q = Queue::lock_free();
+    let workers: Vec<_> = (0..4).map(|_|spawn_thread(&q)).collect();
+    thread::sleep(Duration::from_millis(10));
+    let (tx,rx) = mpsc::channel();
+    for i in 0..100 {
+        let tx = tx.clone();
+        let task = (move||tx.send(i).unwrap()).into_task().task;
+        q.add_boxtask((Box::new(task), Box::new(|_|{})));
+    }
+    let mut got: Vec<i32> = (0..100).map(|_|rx.recv().unwrap()).collect();
+    got.sort();
+    assert_eq!(got, (0..100).collect::<Vec<_>>());
+    for _ in 0..4 {
+        let mut exit = (||{}).into_task();
+        exit.task.kind = Kind::Exit;
+        q.add_boxtask((Box::new(exit.task), Box::new(|_|{})));
+    }
+    workers.into_iter().for_each(|w|w.join().unwrap());
+}
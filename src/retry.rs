@@ -0,0 +1,292 @@
+//! # `retry` module
+//!
+//! A task can declare a [`Retry`] policy at build time, see [`TaskNeed::retry()`](crate::TaskNeed::retry)
+//! and [`TaskNeed::retry_on_err()`](crate::TaskNeed::retry_on_err).
+//!
+//! When an attempt fails, i.e. it panicked or returned an `Err` counted by the policy,
+//! the task is put into its queue again after the backoff, with the same conditions.
+//! Its result is passed on only once, from the last attempt,
+//! so the bound tasks and the result handle never see the failed attempts.
+//! Likewise the task is counted as executed and seen as finished by the observers once,
+//! and a thread serving an exit task only exits after the last attempt.
+//!
+//! The inputs of the task are consumed by each attempt, so the task keeps a copy of them,
+//! which requires the closure and its params to be `Clone`.
+
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cond::TaskId, curry::CallParam, queue::{Home, PostDo, TaskDo}, task::{Kind, Priority, Task, TaskCurrier}, timer::Timer
+};
+
+/// How many times a failing task is run, and how long to wait between the attempts.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{Retry,Backoff};
+/// # use std::time::Duration;
+/// // 1 + 4 retries, waiting 10ms, 20ms, 40ms then 50ms
+/// let policy = Retry::new(5)
+///     .backoff(Backoff::Exponential { initial: Duration::from_millis(10), max: Duration::from_millis(50) });
+/// // only the errors returned count, a panic is not retried
+/// let policy = Retry::new(3).on_panic(false);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retry {
+    max_attempts: u32,
+    backoff: Backoff,
+    on_panic: bool,
+}
+
+/// The wait before the next attempt of a failed task.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// The same wait before each retry.
+    Fixed(Duration),
+    /// The wait doubles after each retry, from `initial` up to `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Retry {
+    /// At most `max_attempts` runs in all, the first one included, at least 1.
+    ///
+    /// By default the task is retried at once, and a panic counts as a failure.
+    pub fn new(max_attempts:u32)->Self {
+        Self { max_attempts: max_attempts.max(1), backoff: Backoff::Fixed(Duration::ZERO), on_panic: true }
+    }
+
+    pub fn backoff(mut self, backoff:Backoff)->Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// whether a panic counts as a failure, true by default.
+    /// if not, the panic is reported as usual and the task is not retried.
+    pub fn on_panic(mut self, yes:bool)->Self {
+        self.on_panic = yes;
+        self
+    }
+
+    /// the wait after the `attempt`-th run failed, counted from 1.
+    pub(crate) fn delay(&self, attempt:u32)->Duration {
+        match self.backoff {
+            Backoff::Fixed(d) => d,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+                initial.saturating_mul(factor).min(max)
+            }
+        }
+    }
+}
+
+pub(crate) type IsErr = dyn Fn(&dyn Any)->bool + Send + Sync;
+
+/// The retry policy of a task, with the means to run it again.
+pub(crate) struct Retrying<C> {
+    pub(crate) policy: Retry,
+    /// copies the task with its params, before they are consumed by an attempt
+    pub(crate) clone: fn(&C)->C,
+    /// whether the result is an error counted as a failure, `None` if only panics count
+    pub(crate) is_err: Option<Arc<IsErr>>,
+}
+
+impl<C> Clone for Retrying<C> {
+    fn clone(&self) -> Self {
+        Self { policy: self.policy, clone: self.clone, is_err: self.is_err.clone() }
+    }
+}
+
+/// Returned by a failed attempt put into the queue again,
+/// the worker does not count it as run, nor exits after it if the task is an exit one.
+pub(crate) struct Retried;
+
+/// A task with a retry policy, owning its postdo which is called once by the last attempt.
+pub(crate) struct RetryTask<C> {
+    task: TaskCurrier<C>,
+    postdo: Box<PostDo>,
+    timer: Timer,
+    /// the queue the task is put into again
+    home: Home,
+    /// the count of the runs so far
+    attempts: u32,
+}
+
+impl<C> RetryTask<C>
+    where
+    TaskCurrier<C>: Task,
+    C: Send + 'static,
+{
+    /// wraps the task, the postdo returned does nothing.
+    pub(crate) fn compile(task:TaskCurrier<C>, postdo:Box<PostDo>, timer:Timer, home:Home)->(Box<TaskDo>,Box<PostDo>) {
+        let task = Self { task, postdo, timer, home, attempts: 0 };
+        (Box::new(task), Box::new(|_|{}))
+    }
+}
+
+impl<C> Task for RetryTask<C>
+    where
+    TaskCurrier<C>: Task,
+    C: Send + 'static,
+{
+    fn run(self:Box<Self>)->Box<dyn Any> {
+        let Self { mut task, postdo, timer, home, attempts } = *self;
+        let attempts = attempts + 1;
+        let Some(retrying) = task.retry.take() else {
+            unreachable!("only the tasks with a retry policy are wrapped");
+        };
+        let spare = (retrying.clone)(&task.currier);
+        let (id,kind,priority) = (task.id,task.kind,task.priority);
+        let r = panic::catch_unwind(AssertUnwindSafe(||Box::new(task).run()));
+        let last = attempts >= retrying.policy.max_attempts;
+        let failed = match &r {
+            Ok(r) => retrying.is_err.as_ref().is_some_and(|is_err|is_err(&**r)),
+            Err(_) => retrying.policy.on_panic,
+        };
+        if last || !failed {
+            match r {
+                Ok(r) => postdo(r),
+                // reported by the worker as usual
+                Err(payload) => panic::resume_unwind(payload),
+            }
+            return Box::new(());
+        }
+        if r.is_err() {
+            // not to be mixed up with a later panic
+            crate::fault::discard_backtrace();
+        }
+        let delay = retrying.policy.delay(attempts);
+        warn!("task#{id:?} failed at attempt {attempts}/{}, retried in {delay:?}.", retrying.policy.max_attempts);
+        let task = TaskCurrier { currier: spare, id, kind, priority, retry: Some(retrying) };
+        let next = Self { task, postdo, timer: timer.clone(), home, attempts };
+        let (_qid,q) = next.home.clone();
+        if delay.is_zero() {
+            q.add_boxtask((Box::new(next),Box::new(|_|{})));
+        } else {
+            // dropped with its postdo if the timer is closed meanwhile
+            timer.schedule(Instant::now() + delay, Box::new(move||q.add_boxtask((Box::new(next),Box::new(|_|{})))));
+        }
+        Box::new(Retried)
+    }
+    fn as_param_mut(&mut self)->Option<&mut dyn CallParam> {
+        self.task.as_param_mut()
+    }
+    fn kind(&self)->Kind {
+        self.task.kind()
+    }
+    fn id(&self)->TaskId {
+        self.task.id()
+    }
+    fn priority(&self)->Priority {
+        self.task.priority()
+    }
+}
+
+#[test]
+fn test_delay() {
+    let ms = Duration::from_millis;
+    let policy = Retry::new(0);
+    assert_eq!(policy.max_attempts, 1);
+    assert_eq!(policy.delay(3), Duration::ZERO);
+    let policy = Retry::new(5).backoff(Backoff::Exponential { initial: ms(10), max: ms(50) });
+    let delays: Vec<_> = (1..6).map(|i|policy.delay(i)).collect();
+    assert_eq!(delays, vec![ms(10),ms(20),ms(40),ms(50),ms(50)]);
+    assert_eq!(policy.delay(100), ms(50));
+}
+
+#[test]
+fn test_retry() {
+    use crate::{Pool, Queue, ResultError, TaskBuildNew};
+    use std::sync::{atomic::{AtomicU32, Ordering}, mpsc};
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    let (tx,rx) = mpsc::channel();
+    pool.on_panic(move|p|tx.send(p.message().to_string()).unwrap());
+    pool.spawn_thread_for(qid);
+
+    // panics twice, and the bound task only sees the last attempt
+    let runs = Arc::new(AtomicU32::new(0));
+    let (sum,sum_handle) = submitter.submit_with_handle((|a:i32|a+1).into_task());
+    let sum = sum.take();
+    let r = runs.clone();
+    let flaky = (move|a:i32|{
+        if r.fetch_add(1, Ordering::Relaxed) < 2 {
+            panic!("flaky");
+        }
+        a*10
+    }).into_task()
+        .bind_to(sum.input_ca::<0>())
+        .retry(Retry::new(3).backoff(Backoff::Fixed(Duration::from_millis(1))));
+    let flaky = submitter.submit(flaky).take();
+    submitter.submit((||4).into_task().bind_to(flaky.input_ca::<0>()));
+    assert_eq!(sum_handle.wait(), Ok(41));
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+    assert!(rx.try_recv().is_err());
+
+    // the errors counted are retried, the last one is passed on
+    let runs = Arc::new(AtomicU32::new(0));
+    let r = runs.clone();
+    let task = (move||Err::<(),_>(r.fetch_add(1, Ordering::Relaxed)))
+        .into_task()
+        .retry_on_err(Retry::new(4), |e:&u32|*e < 2);
+    let (_,handle) = submitter.submit_with_handle(task);
+    assert_eq!(handle.wait(), Ok(Err(2)));
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+
+    // exhausted, the last panic is reported
+    let task = (||->i32{panic!("always")}).into_task().retry(Retry::new(2));
+    let (_,handle) = submitter.submit_with_handle(task);
+    assert_eq!(handle.wait(), Err(ResultError::Abandoned));
+    assert_eq!(rx.recv().unwrap(), "always");
+    assert!(rx.try_recv().is_err());
+
+    // not counted
+    let task = (||->i32{panic!("once")}).into_task().retry(Retry::new(2).on_panic(false));
+    let (_,handle) = submitter.submit_with_handle(task);
+    assert_eq!(handle.wait(), Err(ResultError::Abandoned));
+    assert_eq!(rx.recv().unwrap(), "once");
+    pool.shutdown(crate::Shutdown::Drain);
+}
+
+#[test]
+fn test_retry_exit() {
+    use crate::{Observer, Pool, Queue, TaskBuildNew};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Default)]
+    struct Finished(AtomicU32);
+    impl Observer for Finished {
+        fn finished(&self, _taskid:TaskId, _qid:usize, _run:Duration) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // the only worker of the queue runs all the attempts of an exit task, then exits
+    let mut pool = Pool::new();
+    let finished = Arc::new(Finished::default());
+    pool.add_observer(finished.clone());
+    let queue = Queue::new();
+    let qid = pool.insert_queue(&queue).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+    let runs = Arc::new(AtomicU32::new(0));
+    let r = runs.clone();
+    let task = (move||{
+        if r.fetch_add(1, Ordering::Relaxed) < 2 {
+            panic!("flaky");
+        }
+        7
+    }).into_exit_task().retry(Retry::new(3).backoff(Backoff::Fixed(Duration::from_millis(1))));
+    let (_,handle) = submitter.submit_with_handle(task);
+    pool.spawn_thread_for(qid);
+    pool.join();
+    assert_eq!(handle.try_get(), Ok(7));
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+    // only the last attempt is counted
+    assert_eq!(finished.0.load(Ordering::Relaxed), 1);
+    assert_eq!(queue.stats().executed(), 1);
+}
@@ -0,0 +1,247 @@
+//! # `scaling` module
+//!
+//! Elastic threads for a queue, see [`Pool::scale_for()`](crate::Pool::scale_for).
+//!
+//! The load of the queue is checked by the timer thread of the `Pool`:
+//! threads are spawned while tasks keep waiting, and the idle ones retire after a keep-alive period,
+//! so a bursty queue does not keep idle threads all day.
+
+use std::{
+    fmt::Debug,
+    sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::queue::{spawn_worker, Spawned, WorkerConfig, WorkerCtx};
+use crate::timer::Timer;
+use crate::{Jhandle, Queue};
+
+/// How many threads serve a scaled queue, and when they are spawned or retired.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{Pool, Queue, Scaling, TaskBuildNew, WorkerConfig};
+/// # use std::time::Duration;
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let scaling = Scaling::new(1, 8)
+///     .keep_alive(Duration::from_secs(30))
+///     .max_wait(Duration::from_millis(20))
+///     .worker(WorkerConfig::new().name("burst"));
+/// pool.scale_for(qid, scaling).unwrap();
+///
+/// let submitter = pool.task_submitter(qid).unwrap();
+/// for i in 0..100 {
+///     submitter.submit((move||i*2).into_task());
+/// }
+/// // the scaled threads exit once the queue is drained
+/// pool.join();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scaling {
+    min: usize,
+    max: usize,
+    keep_alive: Duration,
+    max_wait: Duration,
+    worker: WorkerConfig,
+}
+
+impl Scaling {
+    /// At least `min` threads are kept, at most `max` are spawned, `max` is raised to `min` and 1 at least.
+    ///
+    /// By default the idle threads retire after 60s, and threads are spawned once tasks have been waiting for 50ms.
+    pub fn new(min:usize, max:usize)->Self {
+        Self {
+            min,
+            max: max.max(min).max(1),
+            keep_alive: Duration::from_secs(60),
+            max_wait: Duration::from_millis(50),
+            worker: WorkerConfig::new(),
+        }
+    }
+
+    /// how long a thread beyond `min` stays idle before it retires.
+    pub fn keep_alive(mut self, d:Duration)->Self {
+        self.keep_alive = d;
+        self
+    }
+
+    /// how long tasks keep waiting in the queue before more threads are spawned,
+    /// also the period of the load checks is half of it.
+    pub fn max_wait(mut self, d:Duration)->Self {
+        self.max_wait = d.max(Duration::from_millis(1));
+        self
+    }
+
+    /// how the threads are spawned, its count is ignored.
+    pub fn worker(mut self, config:WorkerConfig)->Self {
+        self.worker = config;
+        self
+    }
+}
+
+struct ScalerState {
+    stopped: bool,
+    jhands: Vec<Jhandle>,
+    /// since when the queue has not been empty at each check
+    backlog_since: Option<Instant>,
+}
+
+/// The autoscaler of a queue, shared by the pool, its check job in the timer, and its threads.
+pub(crate) struct Scaler {
+    qid: usize,
+    queue: Queue,
+    scaling: Scaling,
+    ctx: WorkerCtx,
+    spawned: Spawned,
+    /// the count of the threads not exited yet
+    live: AtomicUsize,
+    state: Mutex<ScalerState>,
+}
+
+impl Scaler {
+    pub(crate) fn new(qid:usize, queue:Queue, scaling:Scaling, ctx:WorkerCtx, spawned:Spawned)->Arc<Self> {
+        let state = ScalerState { stopped: false, jhands: Vec::new(), backlog_since: None };
+        Arc::new(Self { qid, queue, scaling, ctx, spawned, live: AtomicUsize::new(0), state: Mutex::new(state) })
+    }
+
+    /// spawns the `min` threads, and starts the load checks in the timer.
+    pub(crate) fn start(self:&Arc<Self>, timer:&Timer) {
+        let mut state = self.state.lock().unwrap();
+        self.spawn(&mut state, self.scaling.min);
+        drop(state);
+        self.schedule_check(timer);
+    }
+
+    pub(crate) fn qid(&self)->usize {
+        self.qid
+    }
+
+    pub(crate) fn keep_alive(&self)->Duration {
+        self.scaling.keep_alive
+    }
+
+    /// the count of the threads not exited yet
+    pub(crate) fn live(&self)->usize {
+        self.live.load(Ordering::Acquire)
+    }
+
+    /// takes one thread off if more than `min` are alive, returns whether the caller may retire.
+    pub(crate) fn retire(&self)->bool {
+        self.live
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live|(live > self.scaling.min).then(||live - 1))
+            .is_ok()
+    }
+
+    /// takes off a thread exited for other reasons, e.g. an exit task or the pool shutdown.
+    pub(crate) fn left(&self) {
+        self.live.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// stops spawning, and gives the threads to be joined by the pool.
+    pub(crate) fn stop(&self)->Vec<Jhandle> {
+        let mut state = self.state.lock().unwrap();
+        state.stopped = true;
+        std::mem::take(&mut state.jhands)
+    }
+
+    fn spawn(self:&Arc<Self>, state:&mut ScalerState, n:usize) {
+        for _ in 0..n {
+            let n = self.spawned.next(self.qid);
+            let mut ctx = WorkerCtx { scaler: Some(self.clone()), ..self.ctx.clone() };
+            let builder = self.scaling.worker.builder(self.qid, n, &mut ctx);
+            self.live.fetch_add(1, Ordering::AcqRel);
+            match spawn_worker(&self.queue, ctx, builder) {
+                Ok(jhandle) => state.jhands.push(jhandle),
+                Err(_e) => {
+                    self.live.fetch_sub(1, Ordering::AcqRel);
+                    error!("failed to spawn a thread for Q#{}: {_e}", self.qid);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn schedule_check(self:&Arc<Self>, timer:&Timer) {
+        let scaler = self.clone();
+        let t = timer.clone();
+        let at = Instant::now() + self.scaling.max_wait / 2;
+        timer.schedule(at, Box::new(move||{
+            if scaler.check() {
+                scaler.schedule_check(&t);
+            }
+        }));
+    }
+
+    /// spawns threads if the queue has not been empty for `max_wait`,
+    /// returns false once stopped.
+    fn check(self:&Arc<Self>)->bool {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            return false;
+        }
+        // the retired ones
+        state.jhands.retain(|j|!j.0.is_finished());
+        let len = self.queue.len();
+        if len == 0 {
+            state.backlog_since = None;
+            return true;
+        }
+        let now = Instant::now();
+        let since = *state.backlog_since.get_or_insert(now);
+        let live = self.live();
+        if now - since >= self.scaling.max_wait && live < self.scaling.max {
+            let n = len.min(self.scaling.max - live);
+            debug!("Q#{} has {len} tasks waiting, {n} threads are spawned besides {live}.", self.qid);
+            self.spawn(&mut state, n);
+            // gives the new threads a chance before spawning more
+            state.backlog_since = None;
+        }
+        true
+    }
+}
+
+impl Debug for Scaler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Scaler{{Q#{},live:{},{:?}}}",self.qid,self.live(),self.scaling)
+    }
+}
+
+#[test]
+fn test_scaling() {
+    use crate::{Pool, TaskBuildNew};
+    use std::thread;
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let scaling = Scaling::new(1, 4)
+        .keep_alive(Duration::from_millis(50))
+        .max_wait(Duration::from_millis(5));
+    pool.scale_for(qid, scaling.clone()).unwrap();
+    assert!(pool.scale_for(qid, scaling).is_none());
+    let scaler = pool.scalers[0].clone();
+    assert_eq!(scaler.live(), 1);
+
+    // a burst
+    let submitter = pool.task_submitter(qid).unwrap();
+    let handles: Vec<_> = (0..8)
+        .map(|i|submitter.submit_with_handle((move||{
+            thread::sleep(Duration::from_millis(30));
+            i
+        }).into_task()).1)
+        .collect();
+    let mut peak = 0;
+    for h in handles {
+        peak = peak.max(scaler.live());
+        h.wait().unwrap();
+    }
+    assert!(peak > 1 && peak <= 4, "{peak}");
+
+    // back to min once idle
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while scaler.live() > 1 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(scaler.live(), 1);
+    pool.join();
+    assert_eq!(scaler.live(), 0);
+}
@@ -0,0 +1,352 @@
+//! # `scope` module
+//!
+//! A scoped pool, see [`scope()`], modeled on [`std::thread::scope`].
+//!
+//! The closures of the tasks submitted in a scope may borrow the data of the caller,
+//! since the scope does not return before all its tasks have been run or dropped:
+//! the threads are drained and joined, and the conditional tasks still waiting are dropped.
+//!
+//! Only the closures may borrow, the conditions and the return values are still `'static`,
+//! since they are passed between the tasks as `dyn Any`.
+
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+};
+
+use crate::{
+    curry::{CallOnce, Currier},
+    meta::TupleCondAddr,
+    handle::result_channel,
+    submitter::{Compilable, TaskInf},
+    task::{PsOf, Release, TaskCurrier},
+    Pool, QueueConfig, ResultHandle, Shutdown, Submission, TaskId, TaskNeed, TaskSubmitter, WorkerConfig,
+};
+
+/// Runs `f` with a scoped pool, and returns once all the tasks submitted in it have been run or dropped.
+///
+/// The queues are created by the scope, see [`Scope::new_queue()`],
+/// so they are served by none but the threads of the scope.
+/// The tasks left in the queues are run by the threads of the scope before it returns,
+/// or dropped if the queue is not served by any thread;
+/// the conditional tasks whose conditions have not all arrived are dropped,
+/// their result handles report [`ResultError::Abandoned`](crate::ResultError::Abandoned).
+///
+/// A retry policy is ignored in a scope, since the closure can not be copied.
+/// A task released to a queue out of the scope, see [`TaskNeed::release_to()`], is rejected,
+/// since the scope could not run it before returning.
+///
+/// # Examples
+/// ```rust
+/// # use taskorch::{scope, QueueConfig, TaskBuildNew};
+/// let data = vec![1, 2, 3, 4, 5, 6];
+/// let sums = scope(|s| {
+///     let qid = s.new_queue(QueueConfig::new()).unwrap();
+///     s.spawn_thread_for(qid);
+///     s.spawn_thread_for(qid);
+///     let submitter = s.task_submitter(qid).unwrap();
+///     // no Arc, the chunks are borrowed
+///     let handles: Vec<_> = data.chunks(2)
+///         .map(|chunk|submitter.submit_with_handle((move||chunk.iter().sum::<i32>()).into_task()).1)
+///         .collect();
+///     handles.into_iter().map(|h|h.wait().unwrap()).collect::<Vec<_>>()
+/// });
+/// assert_eq!(sums, vec![3, 7, 11]);
+/// ```
+pub fn scope<'env,F,T>(f:F)->T
+    where
+    F: for<'scope> FnOnce(&'scope Scope<'scope,'env>)->T,
+{
+    let scope = Scope {
+        pool: Mutex::new(Some(Pool::new())),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+    let r = panic::catch_unwind(AssertUnwindSafe(||f(&scope)));
+    scope.finish();
+    match r {
+        Ok(r) => r,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// A pool whose tasks may borrow from the caller of [`scope()`].
+pub struct Scope<'scope,'env:'scope> {
+    /// taken out when the scope finishes
+    pool: Mutex<Option<Pool>>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope,'env> Scope<'scope,'env> {
+    /// Creates a queue owned by the scope, returns its id, `None` once the scope is finishing.
+    ///
+    /// Unlike [`Pool::insert_queue()`], a queue of the caller is not accepted:
+    /// a thread serving it out of the scope could run a task after the scope has returned.
+    ///
+    /// ```compile_fail
+    /// # use taskorch::{scope, spawn_thread, Queue};
+    /// let queue = Queue::new();
+    /// let _worker = spawn_thread(&queue);
+    /// scope(|s|{
+    ///     s.insert_queue(&queue);
+    /// });
+    /// ```
+    pub fn new_queue(&self, config:QueueConfig)->Option<usize> {
+        self.pool.lock().unwrap().as_mut()?.insert_queue(&config.build())
+    }
+
+    /// Same as [`Pool::spawn_thread_for()`], the thread is joined before the scope returns.
+    pub fn spawn_thread_for(&self, qid:usize)->Option<usize> {
+        self.pool.lock().unwrap().as_mut()?.spawn_thread_for(qid)
+    }
+
+    /// Same as [`Pool::spawn_threads_for()`], the threads are joined before the scope returns.
+    pub fn spawn_threads_for(&self, qid:usize, config:WorkerConfig)->Option<Vec<usize>> {
+        self.pool.lock().unwrap().as_mut()?.spawn_threads_for(qid, config)
+    }
+
+    /// Gets a submitter of tasks borrowing from the scope.
+    pub fn task_submitter(&'scope self, qid:usize)->Option<ScopedSubmitter<'scope>> {
+        let submitter = self.pool.lock().unwrap().as_ref()?.task_submitter(qid)?;
+        Some(ScopedSubmitter { submitter, pool: &self.pool, scope: PhantomData })
+    }
+
+    /// drains the queues, then drops the tasks left.
+    /// the pool is taken out first, for the tasks still running may call the scope.
+    fn finish(&self) {
+        let Some(mut pool) = self.pool.lock().unwrap_or_else(|e|e.into_inner()).take() else {
+            return;
+        };
+        // the panics of the threads are only logged, as when a pool is dropped
+        let _panics = pool.stop_threads(Shutdown::Drain);
+    }
+}
+
+impl Debug for Scope<'_,'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let finished = self.pool.lock().map_or(true, |pool|pool.is_none());
+        write!(f,"Scope{{finished:{finished}}}")
+    }
+}
+
+/// The submitter of a [`Scope`], the same as [`TaskSubmitter`] except that the closures may borrow.
+#[derive(Clone)]
+pub struct ScopedSubmitter<'scope> {
+    submitter: TaskSubmitter,
+    /// to check the queues the tasks are released to
+    pool: &'scope Mutex<Option<Pool>>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> ScopedSubmitter<'scope> {
+    /// Same as [`TaskSubmitter::submit()`].
+    #[allow(private_bounds)]
+    pub fn submit<C,S,MapFn,MapR,ToFn>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->Submission<S::InputPs>
+        where
+        C: Unscoped<'scope,Static=S>,
+        S: CallOnce + PsOf,
+        MapFn: Compilable<S,MapR,ToFn>,
+    {
+        match unscope(taskneed, self.pool) {
+            Ok(taskneed) => self.submitter.submit(taskneed),
+            Err(taskid) => Submission::Rejected(TaskInf::new(taskid)),
+        }
+    }
+
+    /// Same as [`TaskSubmitter::submit_with_handle()`].
+    #[allow(private_bounds)]
+    pub fn submit_with_handle<C,S,MapFn,MapR,ToFn>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->(Submission<S::InputPs>,ResultHandle<S::R>)
+        where
+        C: Unscoped<'scope,Static=S>,
+        S: CallOnce + PsOf,
+        MapFn: Compilable<S,MapR,ToFn>,
+        S::R: 'static + Clone + Send,
+    {
+        match unscope(taskneed, self.pool) {
+            Ok(taskneed) => self.submitter.submit_with_handle(taskneed),
+            // the sender dropped, the handle reports `Abandoned`
+            Err(taskid) => (Submission::Rejected(TaskInf::new(taskid)), result_channel().1),
+        }
+    }
+
+    /// Same as [`TaskSubmitter::is_closed()`], true once the scope is finishing.
+    pub fn is_closed(&self)->bool {
+        self.submitter.is_closed()
+    }
+}
+
+impl Debug for ScopedSubmitter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"ScopedSubmitter{{closed:{}}}",self.is_closed())
+    }
+}
+
+/// A currier whose closure borrows for `'scope`,
+/// converted into one with a boxed closure seen as `'static`.
+pub(crate) trait Unscoped<'scope> {
+    type Static;
+    /// # Safety
+    /// the task must be run or dropped before `'scope` ends, which `scope()` ensures.
+    unsafe fn unscoped(self)->Self::Static;
+}
+
+macro_rules! impl_unscoped {
+    ($($P:ident),*) => {
+        impl<'scope,F,$($P:'static,)* R:'static> Unscoped<'scope> for Currier<F,($($P,)*),R>
+            where
+            F: FnOnce($($P),*)->R + Send + 'scope,
+        {
+            type Static = Currier<Box<dyn FnOnce($($P),*)->R + Send>,($($P,)*),R>;
+            unsafe fn unscoped(self)->Self::Static {
+                self.map_fn(|f|{
+                    let f: Box<dyn FnOnce($($P),*)->R + Send + 'scope> = Box::new(f);
+                    // only the lifetime is changed
+                    unsafe { std::mem::transmute::<Box<dyn FnOnce($($P),*)->R + Send + 'scope>, Box<dyn FnOnce($($P),*)->R + Send>>(f) }
+                })
+            }
+        }
+    };
+}
+
+impl_unscoped!();
+impl_unscoped!(P1);
+impl_unscoped!(P1,P2);
+impl_unscoped!(P1,P2,P3);
+impl_unscoped!(P1,P2,P3,P4);
+impl_unscoped!(P1,P2,P3,P4,P5);
+impl_unscoped!(P1,P2,P3,P4,P5,P6);
+impl_unscoped!(P1,P2,P3,P4,P5,P6,P7);
+impl_unscoped!(P1,P2,P3,P4,P5,P6,P7,P8);
+
+/// converts the task submitted through a `ScopedSubmitter<'scope>`,
+/// the task is dropped if it is released to a queue out of the scope, which `scope()` would not drain.
+fn unscope<'scope,C,MapFn,MapR,ToFn>(taskneed:TaskNeed<C,MapFn,MapR,ToFn>, pool:&Mutex<Option<Pool>>)->Result<TaskNeed<C::Static,MapFn,MapR,ToFn>,TaskId>
+    where
+    C: Unscoped<'scope>,
+    MapR: TupleCondAddr,
+{
+    let TaskNeed { task: TaskCurrier { currier, id, kind, priority, retry }, map, tofn, attr, phantom } = taskneed;
+    if let Some(Release::Queue(_qid,queue)) = &attr.release {
+        let pool = pool.lock().unwrap_or_else(|e|e.into_inner());
+        let inside = pool.as_ref().is_some_and(|pool|pool.queues.values().any(|q|q.same(queue)));
+        if !inside {
+            warn!("Reject task#{id:?}: released to the queue#{_qid} out of the scope.");
+            return Err(id);
+        }
+    }
+    if retry.is_some() {
+        warn!("Ignore the retry policy of task#{id:?}: not supported in a scope.");
+    }
+    // SAFETY: the submitter lives no longer than 'scope, and the scope runs or drops all its tasks before returning.
+    let currier = unsafe { currier.unscoped() };
+    Ok(TaskNeed { task: TaskCurrier { currier, id, kind, priority, retry: None }, map, tofn, attr, phantom })
+}
+
+#[test]
+fn test_scope() {
+    use crate::{spawn_thread, Queue, ResultError, TaskBuildNew};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // the tasks borrow from the stack, and the conditional ones too
+    let data: Vec<usize> = (1..=100).collect();
+    let runs = AtomicUsize::new(0);
+    let (total,waiting) = scope(|s|{
+        let qid = s.new_queue(QueueConfig::new()).unwrap();
+        s.spawn_threads_for(qid, WorkerConfig::new().count(2)).unwrap();
+        let submitter = s.task_submitter(qid).unwrap();
+        let (sum,sum_handle) = submitter.submit_with_handle((|a:usize,b:usize|{
+            runs.fetch_add(1, Ordering::Relaxed);
+            a + b
+        }).into_task());
+        let sum = sum.take();
+        let (half1,half2) = data.split_at(50);
+        for (i,half) in [half1,half2].into_iter().enumerate() {
+            let task = (||{
+                runs.fetch_add(1, Ordering::Relaxed);
+                half.iter().sum::<usize>()
+            }).into_task();
+            match i {
+                0 => submitter.submit(task.bind_to(sum.input_ca::<0>())),
+                _ => submitter.submit(task.bind_to(sum.input_ca::<1>())),
+            };
+        }
+        // never gets its condition, dropped when the scope returns
+        let (_,waiting) = submitter.submit_with_handle((|a:usize|{
+            runs.fetch_add(1, Ordering::Relaxed);
+            a
+        }).into_task());
+        (sum_handle.wait(),waiting)
+    });
+    assert_eq!(total, Ok(5050));
+    assert_eq!(runs.load(Ordering::Relaxed), 3);
+    assert_eq!(waiting.wait(), Err(ResultError::Abandoned));
+
+    // the queued tasks are drained before returning, or dropped if no thread serves the queue
+    let runs = AtomicUsize::new(0);
+    let (served,idle) = scope(|s|{
+        let qid = s.new_queue(QueueConfig::new()).unwrap();
+        let idle = s.new_queue(QueueConfig::new()).unwrap();
+        let served = s.task_submitter(qid).unwrap();
+        let idle = s.task_submitter(idle).unwrap();
+        let handles: Vec<_> = (0..10)
+            .map(|_|served.submit_with_handle((||runs.fetch_add(1, Ordering::Relaxed)).into_task()).1)
+            .collect();
+        let (_,h) = idle.submit_with_handle((||runs.fetch_add(100, Ordering::Relaxed)).into_task());
+        s.spawn_thread_for(qid);
+        (handles,h)
+    });
+    assert_eq!(runs.load(Ordering::Relaxed), 10);
+    assert!(served.into_iter().all(|h|h.wait().is_ok()));
+    assert_eq!(idle.wait(), Err(ResultError::Abandoned));
+
+    // a panic in the scope is resumed once the tasks are done
+    let runs = AtomicUsize::new(0);
+    let r = panic::catch_unwind(AssertUnwindSafe(||scope(|s|{
+        let qid = s.new_queue(QueueConfig::new()).unwrap();
+        s.spawn_thread_for(qid);
+        s.task_submitter(qid).unwrap().submit((||{
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            runs.fetch_add(1, Ordering::Relaxed);
+        }).into_task());
+        panic!("scope");
+    })));
+    assert!(r.is_err());
+    assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+    // released to a queue out of the scope, served by a thread out of it too,
+    // it would run after the borrow ends
+    let mut outer = Pool::new();
+    let outer_queue = Queue::new();
+    let outer_qid = outer.insert_queue(&outer_queue).unwrap();
+    let worker = spawn_thread(&outer_queue);
+    let outer_submitter = outer.task_submitter(outer_qid).unwrap();
+    let text = String::from("borrowed by a task");
+    let runs = AtomicUsize::new(0);
+    let (rejected,released,inner) = scope(|s|{
+        let qid = s.new_queue(QueueConfig::new()).unwrap();
+        let other = s.new_queue(QueueConfig::new()).unwrap();
+        s.spawn_thread_for(other);
+        let submitter = s.task_submitter(qid).unwrap();
+        let (rejected,h) = submitter.submit_with_handle((|n:usize|{
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            runs.fetch_add(1, Ordering::Relaxed);
+            text.len() + n
+        }).into_task().release_to(&outer_submitter));
+        // released to another queue of the scope
+        let other = s.task_submitter(other).unwrap();
+        let (inner,inner_handle) = submitter.submit_with_handle((|n:usize|text.len() + n).into_task().release_to(&other.submitter));
+        other.submit((||0).into_task().bind_to(inner.take().input_ca::<0>()));
+        (matches!(rejected, Submission::Rejected(_)),h,inner_handle)
+    });
+    drop(text);
+    outer_submitter.submit((||{}).into_exit_task());
+    worker.join().unwrap();
+    assert!(rejected);
+    assert_eq!(runs.load(Ordering::Relaxed), 0);
+    assert_eq!(outer.queues[&outer_qid].len(), 0);
+    assert_eq!(released.wait(), Err(ResultError::Abandoned));
+    assert_eq!(inner.wait(), Ok(18));
+}
@@ -0,0 +1,451 @@
+//! # `stats` module
+//!
+//! Runtime metrics of a pool, see [`Pool::stats()`](crate::Pool::stats).
+//!
+//! The metrics are always collected: each queue counts its tasks and times how long they wait and run,
+//! each worker times how long it is busy, and the waitQueue times how long the conditional tasks
+//! wait from their first condition to their release.
+//! They are recorded by a few relaxed atomic operations per task, without any lock,
+//! so they are cheap enough to be left on in production.
+//!
+//! Durations are kept in histograms of power-of-two buckets of microseconds,
+//! precise enough for percentiles at a glance.
+
+use std::{
+    fmt::Debug,
+    sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// the bucket `i` counts the durations below `2^i` µs, the last one all the longer ones.
+const BUCKETS: usize = 32;
+
+/// Records durations into a histogram, lock-free.
+pub(crate) struct Recorder {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    /// in nanoseconds
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_|AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Recorder {
+    pub(crate) fn record(&self, d:Duration) {
+        let micros = d.as_micros() as u64;
+        let i = ((u64::BITS - micros.leading_zeros()) as usize).min(BUCKETS - 1);
+        let nanos = d.as_nanos() as u64;
+        self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(nanos, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self)->Histogram {
+        Histogram {
+            buckets: std::array::from_fn(|i|self.buckets[i].load(Ordering::Relaxed)),
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_nanos(self.sum.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A histogram of durations, in power-of-two buckets of microseconds.
+///
+/// # Example:
+/// ```rust
+/// # use taskorch::{Pool, Queue, TaskBuildNew};
+/// let mut pool = Pool::new();
+/// let qid = pool.insert_queue(&Queue::new()).unwrap();
+/// let submitter = pool.task_submitter(qid).unwrap();
+/// pool.spawn_thread_for(qid);
+/// let (_,handle) = submitter.submit_with_handle((||std::thread::sleep(std::time::Duration::from_millis(1))).into_task());
+/// handle.wait().unwrap();
+///
+/// let stats = pool.stats();
+/// let run_time = stats.queue(qid).unwrap().run_time();
+/// println!("{} tasks run, p99 {:?}, max {:?}", run_time.count(), run_time.percentile(0.99), run_time.max());
+/// pool.shutdown(taskorch::Shutdown::Drain);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    /// the count of the durations recorded
+    pub fn count(&self)->u64 {
+        self.count
+    }
+
+    pub fn sum(&self)->Duration {
+        self.sum
+    }
+
+    /// the mean duration, zero if none is recorded
+    pub fn mean(&self)->Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+        }
+    }
+
+    pub fn max(&self)->Duration {
+        self.max
+    }
+
+    /// the duration below which the fraction `q` of the durations are, e.g. `0.99` for the 99th percentile,
+    /// rounded up to the bound of its bucket, and never above the max.
+    pub fn percentile(&self, q:f64)->Duration {
+        let rank = ((self.count as f64 * q.clamp(0.0, 1.0)).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bound,count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return bound.min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// the upper bound of each bucket with the count of durations below it and above the previous one,
+    /// the last bucket is unbounded and its bound is `Duration::MAX`.
+    pub fn buckets(&self)->impl Iterator<Item=(Duration,u64)> + '_ {
+        self.buckets.iter().enumerate().map(|(i,count)|{
+            let bound = if i == BUCKETS - 1 { Duration::MAX } else { Duration::from_micros(1 << i) };
+            (bound,*count)
+        })
+    }
+}
+
+/// The counters of a queue, shared by its submitters and workers.
+#[derive(Default)]
+pub(crate) struct QueueMeter {
+    submitted: AtomicU64,
+    executed: AtomicU64,
+    panicked: AtomicU64,
+    /// the count of tasks in the queue, only to track the high-water mark
+    len: AtomicUsize,
+    high_water: AtomicUsize,
+    queue_wait: Recorder,
+    run_time: Recorder,
+}
+
+impl QueueMeter {
+    /// a task is put into the queue
+    pub(crate) fn enqueued(&self) {
+        self.submitted.fetch_add(1, Ordering::Relaxed);
+        let len = self.len.fetch_add(1, Ordering::Relaxed) + 1;
+        self.high_water.fetch_max(len, Ordering::Relaxed);
+    }
+
+    /// a task put into the queue at `at` is popped
+    pub(crate) fn dequeued(&self, at:Instant) {
+        self.removed(1);
+        self.queue_wait.record(at.elapsed());
+    }
+
+    /// tasks are dropped from the queue
+    pub(crate) fn removed(&self, n:usize) {
+        let _ = self.len.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |len|Some(len.saturating_sub(n)));
+    }
+
+    /// a task popped from the queue has been run, and panicked if not `ok`
+    pub(crate) fn ran(&self, run:Duration, ok:bool) {
+        self.executed.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.panicked.fetch_add(1, Ordering::Relaxed);
+        }
+        self.run_time.record(run);
+    }
+
+    pub(crate) fn snapshot(&self, qid:usize, len:usize)->QueueStats {
+        QueueStats {
+            qid,
+            submitted: self.submitted.load(Ordering::Relaxed),
+            executed: self.executed.load(Ordering::Relaxed),
+            panicked: self.panicked.load(Ordering::Relaxed),
+            len,
+            high_water: self.high_water.load(Ordering::Relaxed),
+            queue_wait: self.queue_wait.snapshot(),
+            run_time: self.run_time.snapshot(),
+        }
+    }
+}
+
+/// The metrics of a queue, see [`Queue::stats()`](crate::Queue::stats).
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueStats {
+    qid: usize,
+    submitted: u64,
+    executed: u64,
+    panicked: u64,
+    len: usize,
+    high_water: usize,
+    queue_wait: Histogram,
+    run_time: Histogram,
+}
+
+impl QueueStats {
+    /// the id of the queue in the pool, 0 if not got from a pool
+    pub fn qid(&self)->usize {
+        self.qid
+    }
+
+    /// the count of the tasks put into the queue, the released conditional tasks included
+    pub fn submitted(&self)->u64 {
+        self.submitted
+    }
+
+    /// the count of the tasks run, the panicked ones included,
+    /// the failed attempts of a task retried are not counted
+    pub fn executed(&self)->u64 {
+        self.executed
+    }
+
+    pub fn panicked(&self)->u64 {
+        self.panicked
+    }
+
+    /// the count of the tasks in the queue now
+    pub fn len(&self)->usize {
+        self.len
+    }
+
+    pub fn is_empty(&self)->bool {
+        self.len == 0
+    }
+
+    /// the most tasks ever in the queue at once
+    pub fn high_water(&self)->usize {
+        self.high_water
+    }
+
+    /// how long the tasks waited in the queue before being run
+    pub fn queue_wait(&self)->&Histogram {
+        &self.queue_wait
+    }
+
+    /// how long the tasks ran, their postdo included
+    pub fn run_time(&self)->&Histogram {
+        &self.run_time
+    }
+}
+
+/// The busy time of a worker thread.
+pub(crate) struct WorkerMeter {
+    qid: usize,
+    name: Option<String>,
+    started: Instant,
+    /// in nanoseconds
+    busy: AtomicU64,
+    executed: AtomicU64,
+}
+
+impl WorkerMeter {
+    pub(crate) fn ran(&self, run:Duration) {
+        self.busy.fetch_add(run.as_nanos() as u64, Ordering::Relaxed);
+        self.executed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The live worker threads of a pool, shared with their contexts.
+#[derive(Clone, Default)]
+pub(crate) struct Workers(Arc<Mutex<Vec<Arc<WorkerMeter>>>>);
+
+impl Workers {
+    /// registers the current thread as a worker of the queue `qid`
+    pub(crate) fn join(&self, qid:usize)->Arc<WorkerMeter> {
+        let meter = Arc::new(WorkerMeter {
+            qid,
+            name: std::thread::current().name().map(str::to_string),
+            started: Instant::now(),
+            busy: AtomicU64::new(0),
+            executed: AtomicU64::new(0),
+        });
+        self.0.lock().unwrap().push(meter.clone());
+        meter
+    }
+
+    /// unregisters the exiting worker
+    pub(crate) fn leave(&self, meter:&Arc<WorkerMeter>) {
+        self.0.lock().unwrap().retain(|m|!Arc::ptr_eq(m, meter));
+    }
+
+    fn snapshot(&self)->Vec<WorkerStats> {
+        self.0.lock().unwrap().iter().map(|m|{
+            let busy = Duration::from_nanos(m.busy.load(Ordering::Relaxed));
+            WorkerStats {
+                qid: m.qid,
+                name: m.name.clone(),
+                busy,
+                idle: m.started.elapsed().saturating_sub(busy),
+                executed: m.executed.load(Ordering::Relaxed),
+            }
+        }).collect()
+    }
+}
+
+impl Debug for Workers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"Workers{{live:{}}}",self.0.lock().unwrap().len())
+    }
+}
+
+/// The metrics of a live worker thread.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkerStats {
+    qid: usize,
+    name: Option<String>,
+    busy: Duration,
+    idle: Duration,
+    executed: u64,
+}
+
+impl WorkerStats {
+    /// the id of the queue served
+    pub fn qid(&self)->usize {
+        self.qid
+    }
+
+    /// the thread name
+    pub fn name(&self)->Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// how long it has run tasks since it started
+    pub fn busy(&self)->Duration {
+        self.busy
+    }
+
+    /// how long it has waited for tasks since it started
+    pub fn idle(&self)->Duration {
+        self.idle
+    }
+
+    /// the count of the tasks it has run
+    pub fn executed(&self)->u64 {
+        self.executed
+    }
+}
+
+/// A snapshot of the metrics of a pool, see [`Pool::stats()`](crate::Pool::stats).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolStats {
+    queues: Vec<QueueStats>,
+    workers: Vec<WorkerStats>,
+    waiting: usize,
+    condition_wait: Histogram,
+}
+
+impl PoolStats {
+    pub(crate) fn new(mut queues:Vec<QueueStats>, workers:&Workers, waiting:usize, condition_wait:Histogram)->Self {
+        queues.sort_by_key(|q|q.qid);
+        let mut workers = workers.snapshot();
+        workers.sort_by(|a,b|(a.qid,&a.name).cmp(&(b.qid,&b.name)));
+        Self { queues, workers, waiting, condition_wait }
+    }
+
+    /// the metrics of each queue, by qid
+    pub fn queues(&self)->&[QueueStats] {
+        &self.queues
+    }
+
+    pub fn queue(&self, qid:usize)->Option<&QueueStats> {
+        self.queues.iter().find(|q|q.qid == qid)
+    }
+
+    /// the metrics of each live worker thread, by qid and name
+    pub fn workers(&self)->&[WorkerStats] {
+        &self.workers
+    }
+
+    /// the count of the conditional tasks waiting in the waitQueue
+    pub fn waiting(&self)->usize {
+        self.waiting
+    }
+
+    /// how long the conditional tasks waited from their first condition to their release
+    pub fn condition_wait(&self)->&Histogram {
+        &self.condition_wait
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let us = Duration::from_micros;
+    let recorder = Recorder::default();
+    assert_eq!(recorder.snapshot().percentile(0.5), Duration::ZERO);
+    for d in [us(0), us(3), us(3), us(100), us(5000)] {
+        recorder.record(d);
+    }
+    let h = recorder.snapshot();
+    assert_eq!(h.count(), 5);
+    assert_eq!(h.sum(), us(5106));
+    assert_eq!(h.max(), us(5000));
+    assert_eq!(h.percentile(0.2), us(1));
+    // the bound of the bucket of 3µs
+    assert_eq!(h.percentile(0.6), us(4));
+    assert_eq!(h.percentile(0.8), us(128));
+    assert_eq!(h.percentile(1.0), us(5000));
+    let counts: Vec<_> = h.buckets().map(|(_,count)|count).take(14).collect();
+    assert_eq!(counts, vec![1,0,2,0,0,0,0,1,0,0,0,0,0,1]);
+    assert_eq!(h.buckets().last(), Some((Duration::MAX,0)));
+}
+
+#[test]
+fn test_pool_stats() {
+    use crate::{Pool, Queue, TaskBuildNew};
+    let mut pool = Pool::new();
+    let qid = pool.insert_queue(&Queue::new()).unwrap();
+    let submitter = pool.task_submitter(qid).unwrap();
+
+    let (sum,handle) = submitter.submit_with_handle((|a:i32,b:i32|a+b).into_task());
+    let sum = sum.take();
+    submitter.submit((||1).into_task().bind_to(sum.input_ca::<0>()));
+    submitter.submit((||->i32{panic!("counted")}).into_task());
+    let stats = pool.stats();
+    assert_eq!(stats.waiting(), 1);
+    assert_eq!(stats.queue(qid).unwrap().len(), 2);
+    assert!(stats.workers().is_empty());
+
+    pool.spawn_thread_for(qid);
+    submitter.submit((||2).into_task().bind_to(sum.input_ca::<1>()));
+    assert_eq!(handle.wait(), Ok(3));
+    // counted once the postdo has returned, after the result is handed over
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let stats = loop {
+        let stats = pool.stats();
+        if stats.queue(qid).unwrap().executed() == 4 || Instant::now() > deadline {
+            break stats;
+        }
+        std::thread::yield_now();
+    };
+    let queue = stats.queue(qid).unwrap();
+    assert_eq!((queue.submitted(), queue.executed(), queue.panicked(), queue.len()), (4, 4, 1, 0));
+    assert!((2..=3).contains(&queue.high_water()));
+    assert_eq!((queue.queue_wait().count(), queue.run_time().count()), (4, 4));
+    assert_eq!(stats.waiting(), 0);
+    assert_eq!(stats.condition_wait().count(), 1);
+    let [worker] = stats.workers() else {
+        panic!("one worker expected");
+    };
+    assert_eq!((worker.qid(), worker.executed()), (qid, 4));
+    assert_eq!(worker.name(), Some(format!("taskorch-q{qid}-w0").as_str()));
+    assert!(worker.busy() <= queue.run_time().sum());
+    pool.shutdown(crate::Shutdown::Drain);
+}
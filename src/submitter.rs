@@ -1,18 +1,22 @@
 use crate::{
-    cond::{ArgIdx, CondAddr, Section, TaskId}, curry::CallOnce, log::{Level,LEVEL}, meta::{Fndecl, Identical, TupleAt, TupleCondAddr}, queue::{C1map, PostDo, WhenTupleComed}, task::{
-        taskid_next, PsOf, Task, TaskCurrier, TaskMap, TaskNeed
-    }, Queue
+    cond::{ArgIdx, CondAddr, Section, TaskId}, curry::CallOnce, graph::{Producer, Producing}, expire::Expiry, handle::{result_channel, ResultHandle}, log::{LogLevel,LEVEL}, meta::{Fndecl, Identical, TupleAt, TupleCondAddr}, fault::{run_isolated, PanicReport}, periodic::{tick_at, OnDrop, Periodic, PeriodicHandle, Schedule}, queue::{C1map, Cancellation, Home, PostDo, TaskDo, RunGuard, Waiting, WhenTupleComed}, retry::RetryTask, timer::Timer, task::{
+        taskid_next, PsOf, Release, Task, TaskAttr, TaskCurrier, TaskMap, TaskNeed
+    }, GraphBatch, Queue
 };
 
-use std::{any::{type_name, Any, TypeId}, fmt::Debug, marker::PhantomData};
+use std::{any::{type_name, Any, TypeId}, fmt::Debug, marker::PhantomData, num::NonZeroUsize, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, PoisonError}, time::{Duration, Instant}};
 
 /// Represents how a value was inserted into the system or queue.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Submission<Ps> {
     /// - `Added`: The task ID was not present; a new task was inserted.
     Added(TaskInf<Ps>),
     /// - `Updated`: The task ID already existed; the existing task was updated.
     Updated(TaskInf<Ps>),
+    /// - `Rejected`: The pool has been shut down, or the task is submitted in a scope
+    ///   but released to a queue out of it; the task was dropped.
+    Rejected(TaskInf<Ps>),
 }
 
 impl<Ps> Submission<Ps> {
@@ -21,15 +25,21 @@ impl<Ps> Submission<Ps> {
         match self {
             Self::Added(taskinf) => taskinf,
             Self::Updated(taskinf) => taskinf,
+            Self::Rejected(taskinf) => taskinf,
         }
     }
 }
 
 /// Error type for task submission failures
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum TaskSubmitError {
     /// when submit task, if the id has already existed in waitQueue.
     TaskIdAlreadyExists(TaskId),
+    /// when submit a task without conditions, if the bounded queue is full, see `Queue::bounded()`.
+    QueueFull(TaskId),
+    /// when submit task, if the pool has been shut down, see `Pool::shutdown()`.
+    PoolClosed,
 }
 
 /// Information about a submitted task
@@ -97,6 +107,9 @@ pub struct TaskSubmitter {
     pub(crate) qid: usize, // just use in log
     pub(crate) queue: Queue,
     pub(crate) c1map: C1map,
+    pub(crate) timer: Timer,
+    pub(crate) closed: Arc<AtomicBool>,
+    pub(crate) panics: PanicReport,
 }
 
 impl TaskSubmitter {
@@ -155,35 +168,290 @@ impl TaskSubmitter {
     // TODO next: Optimize postdo: if no taskmap and no tofn, maybe use Option<postdo> to None
     // instead of always invoking it indiscriminately. (the present)
     #[allow(private_bounds)]
-    pub fn submit<C,MapFn,MapR,ToFn>(&self,mut taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->Submission<C::InputPs>
+    pub fn submit<C,MapFn,MapR,ToFn>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->Submission<C::InputPs>
         where
-        TaskCurrier<C>: Task,
-        C: CallOnce + Send + 'static,
-        C::R: 'static + Debug,
-        C: PsOf,
-
-        MapFn: Fndecl<(C::R,),MapR> + Send + 'static,
-        MapFn::Pt: From<(<C as CallOnce>::R,)>, // C::R === ? <C as CallOnce>::R
-        MapFn::Pt: Identical<(<C as CallOnce>::R,)>,
-        MapFn::R: TupleCondAddr + Clone,
-
-        ToFn: Send + 'static,
-        for<'a> ToFn: Fndecl<(&'a MapFn::R,),<MapFn::R as TupleCondAddr>::TCA>,
-        for<'a> <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::Pt: From<(&'a MapFn::R,)>,
-        for<'d,'e> (
-            &'d MapFn::R,
-            &'e <MapFn::R as TupleCondAddr>::TCA,
-            // &'b <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::Cat>>::R,
-        ): WhenTupleComed,
-        // here if we use 'd to substitue the 'e, the error occurs. ???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: From<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
-        // if subsitue the 2nd 'a with 'b, will lead to error???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: Identical<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
     {
+        self.submit_keep(taskneed, |_:&C::R|{})
+    }
+
+    /// Same as [`submit()`](Self::submit), and additionally returns a [`ResultHandle`]
+    /// from which the task's return value can be got after it has been run.
+    ///
+    /// The value is cloned into the handle before it is passed to the bound tasks,
+    /// so the return type must be `Clone`.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # use taskorch::{Pool,TaskBuildNew,Queue,ResultError};
+    /// # use std::time::Duration;
+    /// # let mut pool = Pool::new();
+    /// # let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// // the thread exits after `add` completes
+    /// let add = (|a:i32,b:i32|a+b).into_exit_task();
+    /// let (add, handle) = submitter.submit_with_handle(add);
+    /// let add = add.take();
+    /// // not run yet
+    /// assert_eq!(handle.try_get(), Err(ResultError::Pending));
+    ///
+    /// submitter.submit((||(1,2)).into_task()
+    ///     .map_tuple_with(|r:(i32,i32)|r)
+    ///     .bind_all_to((add.input_ca::<0>(),add.input_ca::<1>())));
+    /// pool.spawn_thread_for(qid);
+    ///
+    /// assert_eq!(handle.wait_timeout(Duration::from_secs(10)), Ok(3));
+    /// pool.join();
+    /// ```
+    ///
+    /// # returns
+    /// * `Submission` - same as `submit()`
+    /// * `ResultHandle` - to wait for or poll the task's result
+    #[allow(private_bounds)]
+    pub fn submit_with_handle<C,MapFn,MapR,ToFn>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->(Submission<C::InputPs>,ResultHandle<C::R>)
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        C::R: 'static + Clone + Send,
+    {
+        let (sender,handle) = result_channel();
+        let submission = self.submit_keep(taskneed, move |r:&C::R|sender.send(r.clone()));
+        (submission,handle)
+    }
+
+    /// Same as [`submit()`](Self::submit), but the task is not run before `at`.
+    ///
+    /// The task is held by the timer of the `Pool` and no worker thread is occupied meanwhile.
+    /// A task without conditions is put into the queue when due;
+    /// a conditional task is put into the queue when due and all its conditions have arrived,
+    /// whichever comes last.
+    /// If `at` has passed, it is the same as `submit()`.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # use taskorch::{Pool,TaskBuildNew,Queue};
+    /// # use std::time::{Duration,Instant};
+    /// # let mut pool = Pool::new();
+    /// # let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// let at = Instant::now() + Duration::from_millis(20);
+    /// submitter.submit_at(at, (move||assert!(Instant::now() >= at)).into_exit_task());
+    /// pool.spawn_thread_for(qid);
+    /// pool.join();
+    /// ```
+    #[allow(private_bounds)]
+    pub fn submit_at<C,MapFn,MapR,ToFn>(&self,at:Instant,mut taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->Submission<C::InputPs>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+    {
+        taskneed.attr.not_before = Some(at);
+        self.submit_keep(taskneed, |_:&C::R|{})
+    }
+
+    /// Same as [`submit_at()`](Self::submit_at), with the due time counted from now.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # use taskorch::{Pool,TaskBuildNew,Queue};
+    /// # use std::time::Duration;
+    /// # let mut pool = Pool::new();
+    /// # let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// // debounce: run 50ms later, instead of sleeping in a worker thread.
+    /// submitter.submit_after(Duration::from_millis(50), (||println!("debounced")).into_exit_task());
+    /// pool.spawn_thread_for(qid);
+    /// pool.join();
+    /// ```
+    #[allow(private_bounds)]
+    pub fn submit_after<C,MapFn,MapR,ToFn>(&self,after:Duration,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->Submission<C::InputPs>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+    {
+        self.submit_at(Instant::now() + after, taskneed)
+    }
+
+    /// Submits the task built by `factory` on each tick of the `schedule`, until the returned handle is stopped.
+    ///
+    /// The ticks are driven by the timer of the `Pool`, the first one happens one period later,
+    /// or at the first matched minute for `Schedule::Cron`.
+    /// The task built on each tick is submitted as by [`submit()`](Self::submit),
+    /// so it can be bound to a downstream `CondAddr`,
+    /// the downstream task must be waiting for each tick, e.g. submitted by the factory as well.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # use taskorch::{Pool,TaskBuildNew,Queue,Schedule};
+    /// # use std::time::Duration;
+    /// # let mut pool = Pool::new();
+    /// # let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// let s = submitter.clone();
+    /// let ticker = submitter.submit_periodic(Schedule::FixedRate(Duration::from_millis(10)), move||{
+    ///     // the consumer of this tick
+    ///     let consumer = s.submit((|n:u32|println!("housekeeping #{n}")).into_task()).take();
+    ///     (||1u32).into_task().bind_to(consumer.input_ca::<0>())
+    /// });
+    /// pool.spawn_thread_for(qid);
+    /// while ticker.ticks() < 3 {
+    ///     std::thread::sleep(Duration::from_millis(5));
+    /// }
+    /// ticker.stop();
+    /// submitter.submit((||{}).into_exit_task());
+    /// pool.join();
+    /// ```
+    ///
+    /// A panic raised by `factory` is reported as a task panic, see [`Pool::on_panic()`](crate::Pool::on_panic),
+    /// that tick is skipped and the schedule goes on.
+    ///
+    /// # Panics
+    /// if the period of `Schedule::FixedRate` or `Schedule::FixedDelay` is zero.
+    ///
+    /// # returns
+    /// * `PeriodicHandle` - to stop the schedule or count its ticks
+    #[allow(private_bounds)]
+    pub fn submit_periodic<C,MapFn,MapR,ToFn,F>(&self,schedule:Schedule,factory:F)->PeriodicHandle
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        F: FnMut()->TaskNeed<C,MapFn,MapFn::R,ToFn> + Send + 'static,
+    {
+        if let Schedule::FixedRate(period) | Schedule::FixedDelay(period) = &schedule {
+            assert!(!period.is_zero(), "the period of a recurring task must not be zero");
+        }
+        let handle = PeriodicHandle::new();
+        let first = schedule.first(Instant::now());
+        let periodic = Arc::new(Periodic { schedule, factory: Mutex::new(factory), handle: handle.clone() });
+        if let Some(first) = first {
+            self.schedule_tick::<C,MapFn,MapR,ToFn,F>(periodic, first);
+        } else {
+            warn!("the schedule {:?} never ticks.", periodic.schedule);
+        }
+        handle
+    }
+
+    /// asks the timer to run the tick of the recurring task at `at`, the wall-clock second `secs`.
+    #[allow(private_bounds)]
+    fn schedule_tick<C,MapFn,MapR,ToFn,F>(&self,periodic:Arc<Periodic<F>>,(at,secs):(Instant,u64))
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        F: FnMut()->TaskNeed<C,MapFn,MapFn::R,ToFn> + Send + 'static,
+    {
+        let s = self.clone();
+        self.timer.schedule(at, Box::new(move||s.tick::<C,MapFn,MapR,ToFn,F>(periodic, at, secs)));
+    }
+
+    /// submits the task of this tick, and schedules the next tick.
+    #[allow(private_bounds)]
+    fn tick<C,MapFn,MapR,ToFn,F>(&self,periodic:Arc<Periodic<F>>,at:Instant,secs:u64)
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        F: FnMut()->TaskNeed<C,MapFn,MapFn::R,ToFn> + Send + 'static,
+    {
+        if self.is_closed() {
+            periodic.handle.stop();
+        }
+        if periodic.handle.is_stopped() {
+            debug!("the recurring task is stopped after {} ticks.", periodic.handle.ticks());
+            return;
+        }
+        // a panicking factory only skips this tick, its lock is not left poisoned.
+        let mut built = None;
+        run_isolated(||{
+            built = Some((periodic.factory.lock().unwrap_or_else(PoisonError::into_inner))());
+        }, TaskId::NONE, self.qid, &self.panics, self.c1map.observers());
+        let Some(taskneed) = built else {
+            let next = match periodic.schedule {
+                Schedule::FixedDelay(delay) => Some(tick_at(Instant::now() + delay)),
+                _ => periodic.schedule.next(at, secs),
+            };
+            if let Some(next) = next {
+                self.schedule_tick::<C,MapFn,MapR,ToFn,F>(periodic, next);
+            }
+            return;
+        };
+        periodic.handle.tick();
+        if let Schedule::FixedDelay(delay) = periodic.schedule {
+            // the next tick is scheduled once the task has run or been dropped.
+            let s = self.clone();
+            let next = OnDrop(Some(Box::new(move||{
+                s.schedule_tick::<C,MapFn,MapR,ToFn,F>(periodic, tick_at(Instant::now() + delay));
+            })));
+            self.submit_keep(taskneed, move |_:&C::R|drop(next));
+        } else {
+            self.submit(taskneed);
+            if let Some(next) = periodic.schedule.next(at, secs) {
+                self.schedule_tick::<C,MapFn,MapR,ToFn,F>(periodic, next);
+            } else {
+                warn!("the schedule {:?} never ticks again.", periodic.schedule);
+            }
+        }
+    }
+
+    /// Same as [`submit()`](Self::submit), but waits at most `timeout` for room if the queue is bounded and full,
+    /// see [`Queue::bounded()`](crate::Queue::bounded).
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # use taskorch::{Pool,TaskBuildNew,Queue,TaskSubmitError,TaskId};
+    /// # use std::time::Duration;
+    /// # let mut pool = Pool::new();
+    /// let qid = pool.insert_queue(&Queue::bounded(1)).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// assert!(submitter.submit_timeout(Duration::from_millis(1), (||1).into_task()).is_ok());
+    /// let full = submitter.submit_timeout(Duration::from_millis(1), (||2).into_task());
+    /// assert_eq!(full.unwrap_err(), TaskSubmitError::QueueFull(TaskId::NONE));
+    /// ```
+    ///
+    /// # returns
+    /// * `Submission` - same as `submit()`
+    /// * `TaskSubmitError::QueueFull` - if there is no room in time, the task is dropped.
+    #[allow(private_bounds)]
+    pub fn submit_timeout<C,MapFn,MapR,ToFn>(&self,timeout:Duration,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->Result<Submission<C::InputPs>,TaskSubmitError>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+    {
+        self.submit_within(taskneed, |_:&C::R|{}, Some(timeout))
+    }
+
+    /// the implementation of `submit()`, blocking until there is room in the queue.
+    /// `keep` is called with the task's return value before it is mapped and passed on.
+    #[allow(private_bounds)]
+    fn submit_keep<C,MapFn,MapR,ToFn,Keep>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>,keep:Keep)->Submission<C::InputPs>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        Keep: FnOnce(&C::R) + Send + 'static,
+    {
+        let taskid = taskneed.id();
+        match self.submit_within(taskneed, keep, None) {
+            Ok(submission) => submission,
+            // waiting for room without timeout only fails when closed.
+            Err(_) => Submission::Rejected(TaskInf::new(taskid)),
+        }
+    }
+
+    /// `submit_keep()` with the time to wait for room in the queue, forever if `None`.
+    #[allow(private_bounds)]
+    fn submit_within<C,MapFn,MapR,ToFn,Keep>(&self,mut taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>,keep:Keep,timeout:Option<Duration>)->Result<Submission<C::InputPs>,TaskSubmitError>
+        where
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
+        Keep: FnOnce(&C::R) + Send + 'static,
+    {
+        if self.is_closed() {
+            error!("task#{:?} is rejected: the pool has been shut down.", taskneed.id());
+            return Err(TaskSubmitError::PoolClosed);
+        }
         // postdo maybe added another param of taskid indicating where the value comes from.
         if 0 == taskneed.task.currier.count() {
             let taskid = taskneed.task.id;
-            if LEVEL >= Level::Warn {
+            if LEVEL >= LogLevel::Warn {
                 if let TaskId(Some(_id)) = taskid {
                     warn!("Ignore the taskid {_id:?}: no conditions found for this task.");
                 }
@@ -193,10 +461,24 @@ impl TaskSubmitter {
                 warn!("task#{:?} has existed in queue!!",taskid);
             }
 
-            let taskcompiled = self.compile(taskneed);
-            self.queue.add_boxtask(taskcompiled);
+            if taskneed.attr.expiry.is_some() {
+                warn!("Ignore the deadline of task#{taskid:?}: no conditions to wait for.");
+            }
+            let not_before = taskneed.attr.not_before.take();
+            let home = self.home(None);
+            let (taskcompiled,producer) = self.compile(taskneed,keep,home);
+            self.c1map.producers().register(producer);
+            self.enqueue(taskcompiled, not_before, timeout).map_err(|_|{
+                if self.is_closed() {
+                    error!("task#{taskid:?} is rejected: the pool has been shut down.");
+                    return TaskSubmitError::PoolClosed;
+                }
+                warn!("task#{taskid:?} is dropped: Q#{} is full.", self.qid);
+                TaskSubmitError::QueueFull(taskid)
+            })?;
             debug!("task#{:?} added into Q#{}", taskid, self.qid);
-            Submission::Added(TaskInf::new(taskid))
+            self.c1map.observers().notify(|o|o.submitted(taskid, self.qid, false));
+            Ok(Submission::Added(TaskInf::new(taskid)))
         } else { // with parameters
              // @A, ensure, the task.id is nonzero.
             if taskneed.id().0.is_none() {
@@ -206,15 +488,23 @@ impl TaskSubmitter {
             let TaskId(Some(taskid)) = taskneed.task.id else {
                 unreachable!("task id has feeded in nonzero @A");
             };
-            let taskcompiled = self.compile(taskneed);
-            let inserted = self.c1map.insert(taskcompiled, taskid);
+            let attr = taskneed.attr.take_waiting();
+            let home = self.home(attr.release.as_ref());
+            let (taskcompiled,producer) = self.compile(taskneed,keep,home);
+            let (waiting,deadline) = self.waiting(taskcompiled, attr);
+            self.c1map.producers().register(producer);
+            let not_before = waiting.not_before;
+            let inserted = self.c1map.insert(waiting, taskid);
+            self.schedule_expiry(taskid, deadline);
+            self.schedule_release(taskid, not_before);
+            self.c1map.observers().notify(|o|o.submitted(TaskId(Some(taskid)), self.qid, true));
             if let crate::queue::Inserted::New = inserted {
                 // debug_assert_eq!(Some(taskid),id);
                 debug!("cond-task#{taskid:?} added into waitQueue");
-                Submission::Added(TaskInf::new(TaskId(Some(taskid))))
+                Ok(Submission::Added(TaskInf::new(TaskId(Some(taskid)))))
             } else {
                 warn!("cond-task#{taskid:?} is duplicated and updated in waitQueue!");
-                Submission::Updated(TaskInf::new(TaskId(Some(taskid))))
+                Ok(Submission::Updated(TaskInf::new(TaskId(Some(taskid)))))
             }
         }
     }
@@ -274,34 +564,18 @@ impl TaskSubmitter {
     #[allow(private_bounds)]
     pub fn try_submit<C,MapFn,MapR,ToFn>(&self,mut taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->SummitResult<C::InputPs>
         where
-        TaskCurrier<C>: Task,
-        C: CallOnce + Send + 'static,
-        C::R: 'static + Debug,
-        C: PsOf,
-
-        MapFn: Fndecl<(C::R,),MapR> + Send + 'static,
-        MapFn::Pt: From<(<C as CallOnce>::R,)>,
-        MapFn::Pt: Identical<(<C as CallOnce>::R,)>,
-        MapFn::R: TupleCondAddr + Clone,
-
-        ToFn: Send + 'static,
-        for<'a> ToFn: Fndecl<(&'a MapFn::R,),<MapFn::R as TupleCondAddr>::TCA>,
-        for<'a> <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::Pt: From<(&'a MapFn::R,)>,
-        for<'d,'e> (
-            &'d MapFn::R,
-            &'e <MapFn::R as TupleCondAddr>::TCA,
-            // &'b <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::Cat>>::R,
-        ): WhenTupleComed,
-        // here if we use 'd to substitue the 'e, the error occurs. ???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: From<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
-        // if subsitue the 2nd 'a with 'b, will lead to error???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: Identical<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
     {
+        if self.is_closed() {
+            error!("task#{:?} is rejected: the pool has been shut down.", taskneed.id());
+            return Err(TaskSubmitError::PoolClosed);
+        }
         // postdo maybe added another param of taskid indicating where the value comes from.
         // without parameter
         if 0 == taskneed.task.currier.count() {
             let taskid = taskneed.task.id;
-            if LEVEL >= Level::Warn {
+            if LEVEL >= LogLevel::Warn {
                 if let TaskId(Some(_id)) = taskid {
                     warn!("Ignore the taskid {_id:?}: no conditions found for this task.");
                 }
@@ -312,9 +586,18 @@ impl TaskSubmitter {
                 return Err(TaskSubmitError::TaskIdAlreadyExists(taskid))
             }
 
-            let taskcompiled = self.compile(taskneed);
-            self.queue.add_boxtask(taskcompiled);
+            if taskneed.attr.expiry.is_some() {
+                warn!("Ignore the deadline of task#{taskid:?}: no conditions to wait for.");
+            }
+            let not_before = taskneed.attr.not_before.take();
+            let (taskcompiled,producer) = self.compile(taskneed,|_:&C::R|{},self.home(None));
+            self.c1map.producers().register(producer);
+            if self.enqueue(taskcompiled, not_before, Some(Duration::ZERO)).is_err() {
+                error!("task#{taskid:?} can not be added: Q#{} is full.", self.qid);
+                return Err(TaskSubmitError::QueueFull(taskid));
+            }
             debug!("task#{:?} added into Q#{}", taskid, self.qid);
+            self.c1map.observers().notify(|o|o.submitted(taskid, self.qid, false));
             Ok(TaskInf::new(taskid))
         } else { // with parameters
              // @A, ensure, the task.id is nonzero.
@@ -325,9 +608,17 @@ impl TaskSubmitter {
             let TaskId(Some(taskid)) = taskneed.task.id else {
                 unreachable!("task id has feeded in nonzero @A");
             };
-            let taskcompiled = self.compile(taskneed);
-            let id = self.c1map.try_insert(taskcompiled, taskid);
+            let attr = taskneed.attr.take_waiting();
+            let home = self.home(attr.release.as_ref());
+            let (taskcompiled,producer) = self.compile(taskneed,|_:&C::R|{},home);
+            let (waiting,deadline) = self.waiting(taskcompiled, attr);
+            self.c1map.producers().register(producer);
+            let not_before = waiting.not_before;
+            let id = self.c1map.try_insert(waiting, taskid);
             if id.is_some() {
+                self.schedule_expiry(taskid, deadline);
+                self.schedule_release(taskid, not_before);
+                self.c1map.observers().notify(|o|o.submitted(TaskId(Some(taskid)), self.qid, true));
                 debug_assert_eq!(Some(taskid),id);
                 debug!("cond-task#{taskid:?} added into waitQueue");
                 Ok(TaskInf::new(TaskId(id)))
@@ -338,6 +629,58 @@ impl TaskSubmitter {
         }
     }
 
+    /// whether the pool has been shut down, the tasks submitted then are rejected.
+    pub fn is_closed(&self)->bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Cancels a conditional task which is still waiting for its conditions.
+    ///
+    /// The task is removed from the waitQueue, and the conditions it has received are dropped.
+    /// Its result handle, if any, reports `Abandoned`.
+    /// Conditions delivered to it afterwards are discarded with an error log.
+    ///
+    /// # Examples:
+    /// ```rust
+    /// # use taskorch::{Pool,TaskBuildNew,Queue,Cancellation,TaskId};
+    /// # let mut pool = Pool::new();
+    /// # let qid = pool.insert_queue(&Queue::new()).unwrap();
+    /// # let submitter = pool.task_submitter(qid).unwrap();
+    /// let task = submitter.submit((|_:i32|{}).into_task()).take();
+    /// assert_eq!(submitter.cancel(task.taskid()), Cancellation::Canceled);
+    /// assert_eq!(submitter.cancel(task.taskid()), Cancellation::NotFound);
+    /// // tasks without conditions are never waiting
+    /// assert_eq!(submitter.cancel(TaskId::NONE), Cancellation::NotFound);
+    /// ```
+    ///
+    /// # returns
+    /// * `Cancellation` - whether the task was canceled, already queued or run, or not found.
+    pub fn cancel(&self, taskid:TaskId)->Cancellation {
+        let r = self.c1map.cancel(taskid);
+        if r == Cancellation::Canceled {
+            debug!("cond-task#{taskid:?} canceled and removed from waitQueue");
+        } else {
+            warn!("cond-task#{taskid:?} can not be canceled: {r:?}");
+        }
+        r
+    }
+
+    /// Cancels multiple conditional tasks, see [`cancel()`](Self::cancel).
+    ///
+    /// # returns
+    /// * `Vec<(TaskId,Cancellation)>` - the result for each task id, in the input order.
+    pub fn cancel_all(&self, taskids:impl IntoIterator<Item=TaskId>)->Vec<(TaskId,Cancellation)> {
+        taskids
+            .into_iter()
+            .map(|taskid|(taskid,self.cancel(taskid)))
+            .collect()
+    }
+
+    /// Starts a batch of tasks submitted all at once through this submitter, see [`GraphBatch`].
+    pub fn batch(&self)->GraphBatch {
+        GraphBatch::new(self.clone())
+    }
+
     #[deprecated(
         since="0.3.0",
         note = "Use `submit()` instead for strict type check. \
@@ -346,64 +689,131 @@ impl TaskSubmitter {
     #[allow(private_bounds)]
     pub fn old_submit<C,MapFn,MapR,ToFn>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>)->TaskId
         where
-        TaskCurrier<C>: Task,
-        C: CallOnce + Send + 'static,
-        C::R: 'static + Debug,
-        C: PsOf,
-
-        MapFn: Fndecl<(C::R,),MapR> + Send + 'static,
-        MapFn::Pt: From<(<C as CallOnce>::R,)>,
-        MapFn::Pt: Identical<(<C as CallOnce>::R,)>,
-        MapFn::R: TupleCondAddr + Clone,
-
-        ToFn: Send + 'static,
-        for<'a> ToFn: Fndecl<(&'a MapFn::R,),<MapFn::R as TupleCondAddr>::TCA>,
-        for<'a> <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::Pt: From<(&'a MapFn::R,)>,
-        for<'d,'e> (
-            &'d MapFn::R,
-            &'e <MapFn::R as TupleCondAddr>::TCA,
-            // &'b <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::Cat>>::R,
-        ): WhenTupleComed,
-        // here up if we use 'd to substitue the 'e, the error occurs. ???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA:
-            // if subsitue the 2nd 'a with 'b, will lead to error???
-            From<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R> +
-            Identical<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
+        C: CallOnce + PsOf,
+        MapFn: Compilable<C,MapR,ToFn>,
     {
         self.submit(taskneed).take().taskid
     }
 
 
-    #[allow(private_bounds)]
-    fn compile<C,MapFn,MapR,ToFn>(&self,TaskNeed{task,map:TaskMap(mapfn),tofn,..}:TaskNeed<C,MapFn,MapFn::R,ToFn>)->(Box<dyn Task+Send>,Box<PostDo>)
+    /// puts the task into the queue, waiting for room for `timeout`, see `Queue::add_within()`;
+    /// or asks the timer to do it at `not_before`, with no waiting then.
+    fn enqueue(&self, taskcompiled:(Box<dyn Task+Send>,Box<PostDo>), not_before:Option<Instant>, timeout:Option<Duration>)
+        ->Result<(),(Box<dyn Task+Send>,Box<PostDo>)>
+    {
+        match not_before {
+            Some(at) if at > Instant::now() => {
+                let q = self.queue.clone();
+                self.timer.schedule(at, Box::new(move||q.add_boxtask(taskcompiled)));
+                Ok(())
+            }
+            _ => self.queue.add_within(taskcompiled, timeout, &self.closed),
+        }
+    }
+
+    /// makes the waiting entry, with the deadline counted from now.
+    /// released into the queue of this submitter, unless `release` tells another one.
+    pub(crate) fn waiting(&self, taskcompiled:(Box<dyn Task+Send>,Box<PostDo>), TaskAttr{expiry,not_before,release,..}:TaskAttr)->(Waiting,Option<Instant>) {
+        let mut waiting = Waiting::from(taskcompiled);
+        waiting.not_before = not_before;
+        waiting.home = match release {
+            Some(Release::Producer) => None,
+            release => Some(self.home(release.as_ref())),
+        };
+        let deadline = expiry.map(|Expiry{after,policy}|{
+            let deadline = Instant::now() + after;
+            waiting.expiry = Some((deadline,policy));
+            deadline
+        });
+        (waiting,deadline)
+    }
+
+    /// asks the timer to expire the task at the deadline, if it is still waiting then.
+    pub(crate) fn schedule_expiry(&self, taskid:NonZeroUsize, deadline:Option<Instant>) {
+        let Some(deadline) = deadline else {
+            return;
+        };
+        let c1map = self.c1map.clone();
+        let q = (self.qid,self.queue.clone());
+        self.timer.schedule(deadline, Box::new(move||c1map.expire(taskid, q)));
+        debug!("cond-task#{taskid:?} expires in {:?}", deadline-Instant::now());
+    }
+
+    /// asks the timer to release the task held until `not_before`, if its conditions have all arrived then.
+    pub(crate) fn schedule_release(&self, taskid:NonZeroUsize, not_before:Option<Instant>) {
+        let Some(at) = not_before else {
+            return;
+        };
+        let c1map = self.c1map.clone();
+        let q = (self.qid,self.queue.clone());
+        self.timer.schedule(at, Box::new(move||c1map.release_due(taskid, q)));
+    }
+
+    /// returns the task with its postdo, and its bindings to be registered once it is submitted,
+    /// none if it is bound to no task.
+    pub(crate) fn compile<C,MapFn,MapR,ToFn,Keep>(&self,taskneed:TaskNeed<C,MapFn,MapFn::R,ToFn>,keep:Keep,home:Home)->((Box<TaskDo>,Box<PostDo>),Option<Producer>)
         where
-        TaskCurrier<C>: Task,
-        C: CallOnce + Send + 'static,
-        C::R: 'static + Debug,
-        C: PsOf,
-
-        MapFn: Fndecl<(C::R,),MapR> + Send + 'static,
-        MapFn::Pt: From<(<C as CallOnce>::R,)>,
-        MapFn::Pt: Identical<(<C as CallOnce>::R,)>,
-        MapFn::R: TupleCondAddr + Clone,
-
-        ToFn: Send + 'static,
-        for<'a> ToFn: Fndecl<(&'a MapFn::R,),<MapFn::R as TupleCondAddr>::TCA>,
-        for<'a> <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::Pt: From<(&'a MapFn::R,)>,
-        for<'d,'e> (
-            &'d MapFn::R,
-            &'e <MapFn::R as TupleCondAddr>::TCA,
-            // &'b <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::Cat>>::R,
-        ): WhenTupleComed,
-        // here if we use 'd to substitue the 'e, the error occurs. ???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: From<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
-        // if subsitue the 2nd 'a with 'b, will lead to error???
-        for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: Identical<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
+        C: CallOnce,
+        MapFn: Compilable<C,MapR,ToFn>,
+        Keep: FnOnce(&C::R) + Send + 'static,
+    {
+        MapFn::compile(self, taskneed, keep, home)
+    }
+
+    /// the queue a task is put into when released, or again when retried,
+    /// the producer's one is not known until then, this one stands for it.
+    pub(crate) fn home(&self, release:Option<&Release>)->Home {
+        match release {
+            Some(Release::Queue(qid,queue)) => (*qid,queue.clone()),
+            _ => (self.qid,self.queue.clone()),
+        }
+    }
+}
+
+/// The bounds of a task to be submitted, written once here for all the submitting methods:
+/// `Self` maps the result of `C` into a tuple, and `ToFn` gives the addresses it is delivered to.
+pub(crate) trait Compilable<C:CallOnce,MapR,ToFn>: Fndecl<(C::R,),MapR,R:TupleCondAddr + Clone> + Sized {
+    /// see `TaskSubmitter::compile()`.
+    fn compile<Keep>(submitter:&TaskSubmitter, taskneed:TaskNeed<C,Self,Self::R,ToFn>, keep:Keep, home:Home)->((Box<TaskDo>,Box<PostDo>),Option<Producer>)
+        where Keep: FnOnce(&C::R) + Send + 'static;
+}
+
+impl<C,MapFn,MapR,ToFn> Compilable<C,MapR,ToFn> for MapFn
+    where
+    TaskCurrier<C>: Task,
+    C: CallOnce + Send + 'static,
+    C::R: 'static + Debug,
+    C: PsOf,
+
+    MapFn: Fndecl<(C::R,),MapR> + Send + 'static,
+    MapFn::Pt: From<(<C as CallOnce>::R,)>, // C::R === ? <C as CallOnce>::R
+    MapFn::Pt: Identical<(<C as CallOnce>::R,)>,
+    MapFn::R: TupleCondAddr + Clone,
+
+    ToFn: Send + 'static,
+    for<'a> ToFn: Fndecl<(&'a MapFn::R,),<MapFn::R as TupleCondAddr>::TCA>,
+    for<'a> <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::Pt: From<(&'a MapFn::R,)>,
+    for<'d,'e> (
+        &'d MapFn::R,
+        &'e <MapFn::R as TupleCondAddr>::TCA,
+        // &'b <ToFn as Fndecl<(&'a MapFn::R,), <MapFn::R as TupleCondAddr>::Cat>>::R,
+    ): WhenTupleComed,
+    // here if we use 'd to substitue the 'e, the error occurs. ???
+    for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: From<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
+    // if subsitue the 2nd 'a with 'b, will lead to error???
+    for<'a,'c> &'a <MapFn::R as TupleCondAddr>::TCA: Identical<&'a <ToFn as Fndecl<(&'c MapFn::R,), <MapFn::R as TupleCondAddr>::TCA>>::R>,
+{
+    fn compile<Keep>(submitter:&TaskSubmitter, TaskNeed{mut task,map:TaskMap(mapfn),tofn,attr,..}:TaskNeed<C,Self,Self::R,ToFn>, keep:Keep, home:Home)->((Box<TaskDo>,Box<PostDo>),Option<Producer>)
+        where Keep: FnOnce(&C::R) + Send + 'static,
     {
-        let mk_postdo = |id:TaskId| {
-            let c1map = self.c1map.clone();
-            let c1queue = (self.qid,self.queue.clone());
+        let mk_postdo = |id:TaskId, producing:Option<Producing>| {
+            let c1map = submitter.c1map.clone();
+            let c1queue = (submitter.qid,submitter.queue.clone());
+            let run_guard = RunGuard(id,c1map.clone());
             let postdo = move |r: Box<dyn Any>| {
+                // dropped at the end, or with the postdo if the task panicked.
+                let _run_guard = run_guard;
+                let _producing = producing;
                 let r_from = &id;
                 let _actual_type = r.type_id();
                 let Ok(r) = r.downcast::<C::R>() else {
@@ -417,6 +827,7 @@ impl TaskSubmitter {
                     // return;
                 };
                 let r: C::R = *r;
+                keep(&r);
                 let rtuple = mapfn.call((r,).into());
                 let rcondaddr = tofn.call(ToFn::Pt::from((&rtuple,)));
                 (&rtuple, (&rcondaddr).into()).foreach(r_from, c1map, c1queue);
@@ -432,9 +843,15 @@ impl TaskSubmitter {
         };
 
         let taskid = task.id;
-        let taskdo = Box::new(task);
-        let postdo = Box::new(mk_postdo(taskid));
-        (taskdo,postdo)
+        // the tasks bound to none are left out, they add nothing to validate
+        let producing = (!attr.targets.is_empty()).then(||submitter.c1map.producers().reserve());
+        let producer = producing.as_ref().map(|producing|Producer::new(producing, &mut task, home.0, attr.targets));
+        let postdo = Box::new(mk_postdo(taskid, producing));
+        if task.retry.is_some() {
+            // the postdo is called by the last attempt
+            return (RetryTask::compile(task, postdo, submitter.timer.clone(), home), producer);
+        }
+        ((Box::new(task),postdo),producer)
     }
 }
 
@@ -445,6 +862,9 @@ impl TaskSubmitter {
             qid: 1,
             queue: Queue::new(),
             c1map: C1map::new(),
+            timer: Timer::new(),
+            closed: Arc::new(AtomicBool::new(false)),
+            panics: PanicReport::default(),
         }
     }
 }
@@ -498,7 +918,258 @@ fn test_submmit() {
     assert!(matches!(task,Submission::Added(inf) if inf.taskid == id2));
 }
 
+#[test]
+fn test_cancel() {
+    use crate::task::TaskBuildNew;
+    let s = TaskSubmitter::test_new();
+    let (task,handle) = s.submit_with_handle((|a:i32,b:i32|a+b).into_task());
+    let task = task.take();
+    let feed = (||1).into_task().bind_to(task.input_ca::<0>());
+    s.submit(feed);
+    // run the feeding task, so the 1st condition is filled
+    let (feed,postdo) = s.queue.pop().unwrap();
+    postdo(feed.run());
+
+    let r = s.cancel_all([task.taskid(),TaskId::new(100)]);
+    assert_eq!(r, vec![(task.taskid(),Cancellation::Canceled),(TaskId::new(100),Cancellation::NotFound)]);
+    assert_eq!(handle.try_get(), Err(crate::ResultError::Abandoned));
+
+    // released tasks can not be canceled
+    let task = s.submit((|a:i32|a).into_task()).take();
+    s.submit((||1).into_task().bind_to(task.input_ca::<0>()));
+    let (feed,postdo) = s.queue.pop().unwrap();
+    postdo(feed.run());
+    assert_eq!(s.cancel(task.taskid()), Cancellation::AlreadyQueued);
+    let (task_released,postdo) = s.queue.pop().unwrap();
+    postdo(task_released.run());
+    assert_eq!(s.cancel(task.taskid()), Cancellation::AlreadyRun);
+}
+
+#[test]
+fn test_expire() {
+    use crate::task::TaskBuildNew;
+    use std::{sync::mpsc, time::Duration};
+    let s = TaskSubmitter::test_new();
+    let (tx,rx) = mpsc::channel();
+    s.c1map.on_expire(Box::new(move|e|tx.send(e).unwrap()));
+
+    // dropped
+    let (task,handle) = s.submit_with_handle((|_:i32|{}).into_task().expire_after(Duration::from_millis(1)));
+    let e = rx.recv().unwrap();
+    assert_eq!((e.taskid(),e.missing(),e.action()), (task.take().taskid(),&[0u8][..],crate::ExpireAction::Dropped));
+    assert_eq!(handle.wait(), Err(crate::ResultError::Abandoned));
+
+    // fallback runs in the queue instead
+    let (fb_tx,fb_rx) = mpsc::channel();
+    let task = s.submit((|_:i32|{}).into_task().expire_after_or_else(Duration::from_millis(1), move|id|fb_tx.send(id).unwrap())).take();
+    assert_eq!(rx.recv().unwrap().action(), crate::ExpireAction::Fallback);
+    let (fallback,postdo) = s.queue.pop().unwrap();
+    postdo(fallback.run());
+    assert_eq!(fb_rx.recv().unwrap(), task.taskid());
+
+    // conditions arrived in time
+    let task = s.submit((|_:i32|{}).into_task().expire_after(Duration::from_millis(20))).take();
+    s.submit((||1).into_task().bind_to(task.input_ca::<0>()));
+    let (feed,postdo) = s.queue.pop().unwrap();
+    postdo(feed.run());
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+}
+
+#[test]
+fn test_delay() {
+    use crate::task::TaskBuildNew;
+    use std::{thread, time::Duration};
+    let s = TaskSubmitter::test_new();
+    let wait_queued = || {
+        for _ in 0..100 {
+            if s.queue.len() > 0 {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        false
+    };
+
+    // without conditions, queued when due
+    let at = Instant::now() + Duration::from_millis(20);
+    s.submit_at(at, (||{}).into_task());
+    assert_eq!(s.queue.len(), 0);
+    assert!(wait_queued());
+    assert!(Instant::now() >= at);
+    let (task,postdo) = s.queue.pop().unwrap();
+    postdo(task.run());
+
+    // conditions arrived before due, held until due
+    let at = Instant::now() + Duration::from_millis(20);
+    let task = s.submit_at(at, (|a:i32|a).into_task()).take();
+    s.submit((||1).into_task().bind_to(task.input_ca::<0>()));
+    let (feed,postdo) = s.queue.pop().unwrap();
+    postdo(feed.run());
+    assert_eq!(s.queue.len(), 0);
+    assert!(wait_queued());
+    assert!(Instant::now() >= at);
+    let (task,postdo) = s.queue.pop().unwrap();
+    postdo(task.run());
+
+    // due before conditions arrived, queued when the last one arrives
+    let task = s.submit_after(Duration::from_millis(1), (|a:i32|a).into_task()).take();
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(s.queue.len(), 0);
+    s.submit((||1).into_task().bind_to(task.input_ca::<0>()));
+    let (feed,postdo) = s.queue.pop().unwrap();
+    postdo(feed.run());
+    assert_eq!(s.queue.len(), 1);
+}
+
+#[test]
+fn test_periodic() {
+    use crate::task::TaskBuildNew;
+    use std::{thread, time::Duration};
+    let s = TaskSubmitter::test_new();
+    let wait_len = |n:usize| {
+        for _ in 0..100 {
+            if s.queue.len() >= n {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        false
+    };
+
+    // fixed rate, whether the tasks have run or not
+    let ticker = s.submit_periodic(Schedule::FixedRate(Duration::from_millis(5)), ||(||{}).into_task());
+    assert!(wait_len(3));
+    ticker.stop();
+    let ticks = ticker.ticks();
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(ticker.ticks(), ticks);
+    while let Some((task,postdo)) = s.queue.pop() {
+        postdo(task.run());
+    }
+
+    // fixed delay, the next tick after the task has run
+    let ticker = s.submit_periodic(Schedule::FixedDelay(Duration::from_millis(1)), ||(||{}).into_task());
+    assert!(wait_len(1));
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!((ticker.ticks(),s.queue.len()), (1,1));
+    let (task,postdo) = s.queue.pop().unwrap();
+    postdo(task.run());
+    assert!(wait_len(1));
+    assert_eq!(ticker.ticks(), 2);
+    ticker.stop();
+    let (task,postdo) = s.queue.pop().unwrap();
+    postdo(task.run());
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!((ticker.ticks(),s.queue.len()), (2,0));
+
+    // a panicking factory is reported and skips its tick, the schedule goes on
+    let (tx,rx) = std::sync::mpsc::channel();
+    s.panics.set(Box::new(move|p|tx.send(p.message().to_string()).unwrap()));
+    let mut calls = 0;
+    let ticker = s.submit_periodic(Schedule::FixedRate(Duration::from_millis(5)), move||{
+        calls += 1;
+        assert!(calls != 2, "factory");
+        (||{}).into_task()
+    });
+    assert!(wait_len(2));
+    ticker.stop();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "factory");
+    assert!(ticker.ticks() >= 2);
+}
+
+#[test]
+fn test_bounded() {
+    use crate::task::TaskBuildNew;
+    use std::{sync::mpsc, thread, time::Duration};
+    let mut s = TaskSubmitter::test_new();
+    s.queue = Queue::bounded(2);
+
+    s.submit((||{}).into_task());
+    assert!(s.try_submit((||{}).into_task()).is_ok());
+    assert_eq!(s.try_submit((||{}).into_task()).unwrap_err(), TaskSubmitError::QueueFull(TaskId::NONE));
+    let start = Instant::now();
+    assert!(s.submit_timeout(Duration::from_millis(10), (||{}).into_task()).is_err());
+    assert!(start.elapsed() >= Duration::from_millis(10));
+
+    // blocks until there is room
+    let (tx,rx) = mpsc::channel();
+    let s2 = s.clone();
+    let blocked = thread::spawn(move||{
+        s2.submit((||{}).into_task());
+        tx.send(()).unwrap();
+    });
+    assert!(rx.recv_timeout(Duration::from_millis(20)).is_err());
+    let (task,postdo) = s.queue.pop().unwrap();
+    postdo(task.run());
+    rx.recv().unwrap();
+    blocked.join().unwrap();
+    assert_eq!(s.queue.len(), 2);
+
+    // conditional tasks are released beyond the capacity
+    while let Some((task,postdo)) = s.queue.pop() {
+        postdo(task.run());
+    }
+    let cond = s.submit((|_:i32|{}).into_task()).take();
+    s.submit((||1).into_task().bind_to(cond.input_ca::<0>()));
+    let (feed,postdo) = s.queue.pop().unwrap();
+    s.submit((||{}).into_task());
+    s.submit((||{}).into_task());
+    assert_eq!(s.queue.len(), 2);
+    postdo(feed.run());
+    assert_eq!(s.queue.len(), 3);
+
+    // the recurring tasks are put in by the timer beyond the capacity, not blocking it
+    let ticker = s.submit_periodic(Schedule::FixedRate(Duration::from_millis(5)), ||(||{}).into_task());
+    let (tx,rx) = mpsc::channel();
+    let expiring = s.submit((|_:i32|{}).into_task()
+        .expire_after_or_else(Duration::from_millis(10), move|id|tx.send(id).unwrap())).take();
+    for _ in 0..100 {
+        if s.queue.len() >= 6 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    ticker.stop();
+    assert!(s.queue.len() >= 6);
+    // the fallback is queued by the timer as well
+    while let Some((task,postdo)) = s.queue.pop() {
+        postdo(task.run());
+    }
+    assert_eq!(rx.recv_timeout(Duration::from_millis(500)), Ok(expiring.taskid()));
+}
+
 #[test]
 fn test_taskinf() {
     let _taskinf = TaskInf::<(i32,)>::new(TaskId::new(3));
 }
+
+#[test]
+fn test_release_home() {
+    use crate::task::TaskBuildNew;
+    let cpu = TaskSubmitter::test_new();
+    let io = TaskSubmitter { qid: 2, queue: Queue::new(), ..cpu.clone() };
+    let run = |s:&TaskSubmitter| {
+        let (task,postdo) = s.queue.pop().unwrap();
+        postdo(task.run());
+    };
+
+    // released into the queue it is submitted through, not the producer's
+    let save = io.submit((|_:i32|{}).into_task()).take();
+    cpu.submit((||1).into_task().bind_to(save.input_ca::<0>()));
+    run(&cpu);
+    assert_eq!((cpu.queue.len(),io.queue.len()), (0,1));
+    run(&io);
+
+    // overridden per task
+    let save = cpu.submit((|_:i32|{}).into_task().release_to(&io)).take();
+    cpu.submit((||1).into_task().bind_to(save.input_ca::<0>()));
+    run(&cpu);
+    assert_eq!((cpu.queue.len(),io.queue.len()), (0,1));
+    run(&io);
+
+    let save = io.submit((|_:i32|{}).into_task().release_to_producer()).take();
+    cpu.submit((||1).into_task().bind_to(save.input_ca::<0>()));
+    run(&cpu);
+    assert_eq!((cpu.queue.len(),io.queue.len()), (1,0));
+    run(&cpu);
+}
@@ -38,9 +38,14 @@
 //! 
 
 use std::{
-    any::Any, marker::PhantomData, sync::atomic::{AtomicUsize, Ordering}
+    any::Any, marker::PhantomData, sync::{atomic::{AtomicUsize, Ordering}, Arc}, time::{Duration, Instant}
 };
 
+use crate::expire::{ExpirePolicy, Expiry};
+use crate::retry::{Retry, Retrying};
+use crate::graph::Target;
+use crate::{Queue, TaskSubmitter};
+
 use crate::{cond::{ArgIdx, CondAddr, Section::Input, TaskId}, curry::{CallOnce, CallParam, Currier}, meta::{TupleAt, TupleCondAddr, TupleOpt}};
 use crate::meta::Fndecl;
 
@@ -56,6 +61,35 @@ pub enum Kind {
     Exit,
 }
 
+/// The priority of a task in its queue, the higher runs first.
+///
+/// Tasks of the same priority run in the order they were put into the queue.
+/// Conditional tasks are put into the queue when all their conditions have arrived,
+/// so their priority applies from then.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct Priority(pub u8);
+
+impl Priority {
+    pub const LOWEST: Self = Self(0);
+    pub const LOW: Self = Self(64);
+    /// the default priority
+    pub const NORMAL: Self = Self(128);
+    pub const HIGH: Self = Self(192);
+    pub const HIGHEST: Self = Self(255);
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+impl From<u8> for Priority {
+    fn from(p:u8)->Self {
+        Self(p)
+    }
+}
+
 static TASKID:TaskIdGen = TaskIdGen::new();
 
 struct TaskIdGen {
@@ -94,6 +128,7 @@ pub(crate) trait Task
     fn kind(&self)->Kind;
     #[allow(dead_code)]
     fn id(&self)->TaskId;
+    fn priority(&self)->Priority;
 }
 
 
@@ -102,6 +137,8 @@ pub(crate) struct TaskCurrier<Currier> {
     pub(crate) currier: Currier,
     pub(crate) id: TaskId,
     pub(crate) kind: Kind,
+    pub(crate) priority: Priority,
+    pub(crate) retry: Option<Retrying<Currier>>,
 }
 
 pub(crate) struct TaskMap<MapFn>(pub(crate) MapFn);
@@ -129,6 +166,9 @@ impl<T> Task for TaskCurrier<T>
     fn id(&self)->TaskId {
         self.id
     }
+    fn priority(&self)->Priority {
+        self.priority
+    }
 }
 
 pub struct TaskNeed<C,MapFn,MapR,ToFn>
@@ -137,14 +177,56 @@ pub struct TaskNeed<C,MapFn,MapR,ToFn>
     pub(crate) task: TaskCurrier<C>,
     pub(crate) map: TaskMap<MapFn>,
     pub(crate) tofn: ToFn,
+    pub(crate) attr: TaskAttr,
     pub(crate) phantom: PhantomData<MapR>
 }
 
+/// The optional attributes of a task, set by the builder methods of `TaskNeed`.
+#[derive(Default)]
+pub(crate) struct TaskAttr {
+    pub(crate) expiry: Option<Expiry>,
+    /// the task is not put into its queue before then, see `TaskSubmitter::submit_at()`
+    pub(crate) not_before: Option<Instant>,
+    /// where the conditional task is put when released, the queue it is submitted through by default
+    pub(crate) release: Option<Release>,
+    /// the addresses the results are bound to, for the validation of the graph
+    pub(crate) targets: Vec<Target>,
+}
+
+impl TaskAttr {
+    /// takes the attributes applied by the waitQueue, the targets are left to be compiled with the task.
+    pub(crate) fn take_waiting(&mut self)->Self {
+        let targets = std::mem::take(&mut self.targets);
+        std::mem::replace(self, Self { targets, ..Self::default() })
+    }
+}
+
+/// Where a conditional task is put once all its conditions have arrived.
+pub(crate) enum Release {
+    /// the queue of a submitter, with its id for logs
+    Queue(usize,Queue),
+    /// the queue of the task delivering the last condition
+    Producer,
+}
+
 impl<C,MapFn,MapR:TupleCondAddr,ToFn> TaskNeed<C,MapFn,MapR,ToFn> {
     /// get task id from task.
     pub fn id(&self)->TaskId {
         self.task.id
     }
+
+    /// Sets the priority of the task in its queue, `Priority::NORMAL` by default.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{TaskBuildNew,Priority};
+    /// let urgent = (|a:i32|a).into_task().priority(Priority::HIGH);
+    /// let housekeeping = (||{}).into_task().priority(Priority(10));
+    /// ```
+    pub fn priority(mut self, p:impl Into<Priority>)->Self {
+        self.task.priority = p.into();
+        self
+    }
 }
 
 #[test]
@@ -198,12 +280,15 @@ impl<F,TC,R,MapFn1,R1,ToFn1> TaskNeed<Currier<F,TC,R>, MapFn1,R1,ToFn1>
         >
     {
         let map  = TaskMap(PassthroughMapFn::<R>::NULL);
+        let mut attr = self.attr;
+        attr.targets = Target::of(&ca).into_iter().collect();
         let tofn = OneToOne::<(R,)>((ca,));
         // let tofn = move |_:&(R,)| ca;
         TaskNeed {
             task: self.task,
             map,
             tofn,
+            attr,
             phantom: PhantomData,
         }
     }
@@ -286,10 +371,13 @@ impl<F,TC,R,MapFn1,R1,ToFn1> TaskNeed<Currier<F,TC,R>, MapFn1,R1,ToFn1>
         MapR::TCA: Default,
         MapFn: Fndecl<(R,),MapR>,
     {
+        let mut attr = self.attr;
+        attr.targets.clear();
         TaskNeed {
             task: self.task,
             map: TaskMap(mapfn),
             tofn: OneToOne::<MapR>::ONETOONE,
+            attr,
             phantom: PhantomData,
         }
     }
@@ -346,6 +434,7 @@ impl<F,TC,R,MapFn1,R1> TaskNeed<Currier<F,TC,R>, MapFn1,R1,OneToOne<R1>>
     /// assert!(task.is_ok());
     /// ```
     pub fn bind_all_to(mut self, cats: R1::TCA)->Self {
+        self.attr.targets = Target::all::<R1>(&cats);
         self.tofn.0 = cats;
         self
     }
@@ -402,6 +491,152 @@ impl<F,TC,R,MapFn1,R1> TaskNeed<Currier<F,TC,R>, MapFn1,R1,OneToOne<R1>>
     }
 }
 
+impl<F,TC,R,MapFn1,R1,ToFn1> TaskNeed<Currier<F,TC,R>, MapFn1,R1,ToFn1>
+    where
+    TC: TupleOpt,
+    R1: TupleCondAddr,
+{
+    /// Sets a deadline for the task to wait for its conditions, counted from its submission.
+    ///
+    /// If not all conditions arrive in time, the task is dropped,
+    /// and its result handle, if any, reports `Abandoned`.
+    /// Tasks without conditions never wait, and the deadline is ignored.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::TaskBuildNew;
+    /// # use std::time::Duration;
+    /// let task = (|_:i32|{}).into_task().expire_after(Duration::from_secs(5));
+    /// ```
+    pub fn expire_after(mut self, after:Duration)->Self {
+        self.attr.expiry = Some(Expiry { after, policy: ExpirePolicy::Drop });
+        self
+    }
+
+    /// Same as [`expire_after()`](Self::expire_after), but runs `fallback` in the task's queue
+    /// instead of the task. The id of the expired task is passed to `fallback`.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::TaskBuildNew;
+    /// # use std::time::Duration;
+    /// let task = (|_:i32|{})
+    ///     .into_task()
+    ///     .expire_after_or_else(Duration::from_secs(5), |id|println!("task#{id:?} expired"));
+    /// ```
+    pub fn expire_after_or_else<Fb>(mut self, after:Duration, fallback:Fb)->Self
+        where Fb: FnOnce(TaskId) + Send + 'static
+    {
+        self.attr.expiry = Some(Expiry { after, policy: ExpirePolicy::Fallback(Box::new(fallback)) });
+        self
+    }
+
+    /// Same as [`expire_after()`](Self::expire_after), but fills the missing conditions
+    /// with their default values and runs the task, its result is passed on as usual.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::TaskBuildNew;
+    /// # use std::time::Duration;
+    /// // `b` is 0 if not arrived in 5 seconds
+    /// let task = (|a:i32,b:i32|a+b).into_task().expire_after_or_default(Duration::from_secs(5));
+    /// ```
+    pub fn expire_after_or_default(mut self, after:Duration)->Self
+        where TC: Default + Send + 'static
+    {
+        self.attr.expiry = Some(Expiry { after, policy: ExpirePolicy::Defaults(Box::new(TC::default())) });
+        self
+    }
+
+    /// Runs the task again with the same conditions, after a backoff, if it panics,
+    /// as many times as allowed by the policy; the result of the last attempt is passed on.
+    ///
+    /// The closure and the params must be `Clone`, for each attempt consumes them.
+    ///
+    /// While waiting for its backoff, the task is held by the timer of the pool, not by its queue:
+    /// if the pool is shut down meanwhile, even by [`Shutdown::Drain`](crate::Shutdown::Drain),
+    /// it is dropped without another attempt, and its result handle reports `Abandoned`.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{TaskBuildNew,Retry,Backoff};
+    /// # use std::time::Duration;
+    /// let task = (|url:String|println!("fetching {url}"))
+    ///     .into_task()
+    ///     .retry(Retry::new(3).backoff(Backoff::Fixed(Duration::from_millis(100))));
+    /// ```
+    pub fn retry(mut self, policy:Retry)->Self
+        where
+        F: Clone,
+        TC::Opt: Clone,
+    {
+        self.task.retry = Some(Retrying { policy, clone: Currier::clone, is_err: None });
+        self
+    }
+
+    /// Puts the task into the queue of `submitter` once all its conditions have arrived,
+    /// instead of the queue it is submitted through.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{Pool,Queue,TaskBuildNew};
+    /// let mut pool = Pool::new();
+    /// let cpu = pool.insert_queue(&Queue::new()).unwrap();
+    /// let io = pool.insert_queue(&Queue::new()).unwrap();
+    /// let cpu_submitter = pool.task_submitter(cpu).unwrap();
+    /// let io_submitter = pool.task_submitter(io).unwrap();
+    /// // submitted along with its producer, but run by the threads of the io queue
+    /// let save = (|n:i32|println!("saving {n}")).into_task().release_to(&io_submitter);
+    /// let save = cpu_submitter.submit(save).take();
+    /// cpu_submitter.submit((||1+1).into_task().bind_to(save.input_ca::<0>()));
+    /// ```
+    pub fn release_to(mut self, submitter:&TaskSubmitter)->Self {
+        self.attr.release = Some(Release::Queue(submitter.qid, submitter.queue.clone()));
+        self
+    }
+
+    /// Puts the task into the queue of the task delivering its last condition,
+    /// instead of the queue it is submitted through.
+    ///
+    /// In work-stealing mode, the task is then put into the local deque of the thread having run the producer,
+    /// see [`Queue::work_stealing()`].
+    pub fn release_to_producer(mut self)->Self {
+        self.attr.release = Some(Release::Producer);
+        self
+    }
+}
+
+impl<F,TC,T,E,MapFn1,R1,ToFn1> TaskNeed<Currier<F,TC,Result<T,E>>, MapFn1,R1,ToFn1>
+    where
+    TC: TupleOpt,
+    R1: TupleCondAddr,
+{
+    /// Same as [`retry()`](Self::retry), and an `Err` for which `counts` returns true is a failure as well.
+    ///
+    /// # Example:
+    /// ```rust
+    /// # use taskorch::{TaskBuildNew,Retry};
+    /// # use std::io::{Error,ErrorKind};
+    /// let task = (|path:String|std::fs::read_to_string(path))
+    ///     .into_task()
+    ///     .retry_on_err(Retry::new(3), |e:&Error|e.kind() == ErrorKind::Interrupted);
+    /// ```
+    pub fn retry_on_err<P>(mut self, policy:Retry, counts:P)->Self
+        where
+        F: Clone,
+        TC::Opt: Clone,
+        T: 'static,
+        E: 'static,
+        P: Fn(&E)->bool + Send + Sync + 'static,
+    {
+        let is_err = move |r:&dyn Any|r
+            .downcast_ref::<Result<T,E>>()
+            .is_some_and(|r|r.as_ref().is_err_and(&counts));
+        self.task.retry = Some(Retrying { policy, clone: Currier::clone, is_err: Some(Arc::new(is_err)) });
+        self
+    }
+}
+
 // Internal use only
 // just for keep the origin type of task input
 // TODO: maybe will be merged into CallOnce, at present, use this
@@ -550,9 +785,12 @@ impl<F:FnOnce()->R,R> TaskBuildNew<Currier<F,(),R>,PassthroughMapFn<R>,(R,),OneT
                 currier: Currier::from(self),
                 id: TaskId::NONE,
                 kind: Kind::Normal,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -571,9 +809,12 @@ impl<F:FnOnce()->R,R> TaskBuildNew<Currier<F,(),R>,PassthroughMapFn<R>,(R,),OneT
                 currier: Currier::from(self),
                 id: TaskId::NONE,
                 kind: Kind::Exit,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -595,9 +836,12 @@ impl<F:FnOnce()->R,R> TaskBuildNew<Currier<F,(),R>,PassthroughMapFn<R>,(R,),OneT
                 currier: Currier::from(self.0),
                 id: self.1,
                 kind: Kind::Normal,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -616,9 +860,12 @@ impl<F:FnOnce()->R,R> TaskBuildNew<Currier<F,(),R>,PassthroughMapFn<R>,(R,),OneT
                 currier: Currier::from(self.0),
                 id: self.1,
                 kind: Kind::Exit,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -642,9 +889,12 @@ impl<F:FnOnce(P1)->R,P1,R> TaskBuildNew<Currier<F,(P1,),R>,PassthroughMapFn<R>,(
                 currier: Currier::from(self),
                 id: TaskId::NONE,
                 kind: Kind::Normal,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -663,9 +913,12 @@ impl<F:FnOnce(P1)->R,P1,R> TaskBuildNew<Currier<F,(P1,),R>,PassthroughMapFn<R>,(
                 currier: Currier::from(self),
                 id: TaskId::NONE,
                 kind: Kind::Exit,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -687,9 +940,12 @@ impl<F:FnOnce(P1,)->R,P1,R> TaskBuildNew<Currier<F,(P1,),R>,PassthroughMapFn<R>,
                 currier: Currier::from(self.0),
                 id: self.1,
                 kind: Kind::Normal,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -708,9 +964,12 @@ impl<F:FnOnce(P1,)->R,P1,R> TaskBuildNew<Currier<F,(P1,),R>,PassthroughMapFn<R>,
                 currier: Currier::from(self.0),
                 id: self.1,
                 kind: Kind::Exit,
+                priority: Priority::NORMAL,
+                retry: None,
             },
             map: TaskMap(PassthroughMapFn::NULL),
             tofn: OneToOne::ONETOONE,
+            attr: TaskAttr::default(),
             phantom: PhantomData,
         }
     }
@@ -735,9 +994,12 @@ macro_rules! impl_task_build_new {
                         currier: Currier::from(self),
                         id: TaskId::NONE,
                         kind: Kind::Normal,
+                        priority: Priority::NORMAL,
+                        retry: None,
                     },
                     map: TaskMap(PassthroughMapFn::NULL),
                     tofn: OneToOne::ONETOONE,
+                    attr: TaskAttr::default(),
                     phantom: PhantomData,
                 }
             }
@@ -756,9 +1018,12 @@ macro_rules! impl_task_build_new {
                         currier: Currier::from(self),
                         id: TaskId::NONE,
                         kind: Kind::Exit,
+                        priority: Priority::NORMAL,
+                        retry: None,
                     },
                     map: TaskMap(PassthroughMapFn::NULL),
                     tofn: OneToOne::ONETOONE,
+                    attr: TaskAttr::default(),
                     phantom: PhantomData,
                 }
             }
@@ -780,9 +1045,12 @@ macro_rules! impl_task_build_new {
                         currier: Currier::from(self.0),
                         id: self.1,
                         kind: Kind::Normal,
+                        priority: Priority::NORMAL,
+                        retry: None,
                     },
                     map: TaskMap(PassthroughMapFn::NULL),
                     tofn: OneToOne::ONETOONE,
+                    attr: TaskAttr::default(),
                     phantom: PhantomData,
                 }
             }
@@ -801,9 +1069,12 @@ macro_rules! impl_task_build_new {
                         currier: Currier::from(self.0),
                         id: self.1,
                         kind: Kind::Exit,
+                        priority: Priority::NORMAL,
+                        retry: None,
                     },
                     map: TaskMap(PassthroughMapFn::NULL),
                     tofn: OneToOne::ONETOONE,
+                    attr: TaskAttr::default(),
                     phantom: PhantomData,
                 }
             }
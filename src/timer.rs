@@ -0,0 +1,159 @@
+//! The timer owned by a `Pool`, running jobs at their due time on a background thread.
+//!
+//! The thread is started on the first scheduled job, and stopped by `close()`,
+//! the jobs not due yet are dropped then.
+
+use std::{
+    cell::Cell,
+    cmp::Ordering,
+    collections::BinaryHeap,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+pub(crate) type TimerDo = dyn FnOnce() + Send;
+
+thread_local! {
+    /// set on the timer threads, whose jobs must not block on a full queue
+    static ON_TIMER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// whether the current thread is the timer thread of a pool.
+pub(crate) fn is_timer_thread()->bool {
+    ON_TIMER.get()
+}
+
+struct Entry {
+    at: Instant,
+    // keeps the scheduling order of the jobs due at the same instant
+    seq: u64,
+    job: Box<TimerDo>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Entry {}
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Entry {
+    // reversed, for the earliest to be on the top of the max-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.at.cmp(&self.at).then(other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    heap: BinaryHeap<Entry>,
+    seq: u64,
+    closed: bool,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Timer(Arc<(Mutex<State>,Condvar)>);
+
+impl Timer {
+    pub(crate) fn new()->Self {
+        Self::default()
+    }
+
+    /// schedules the job to run at `at`, or as soon as possible if `at` has passed.
+    /// returns false and drops the job if the timer has been closed.
+    pub(crate) fn schedule(&self, at:Instant, job:Box<TimerDo>)->bool {
+        let mut state = self.0.0.lock().unwrap();
+        if state.closed {
+            warn!("the timer has been closed, the job is dropped.");
+            return false;
+        }
+        state.seq += 1;
+        let seq = state.seq;
+        state.heap.push(Entry { at, seq, job });
+        if state.thread.is_none() {
+            let timer = self.clone();
+            state.thread = Some(thread::spawn(move||timer.run()));
+        }
+        self.0.1.notify_one();
+        true
+    }
+
+    /// the count of the jobs not run yet
+    #[allow(dead_code)]
+    pub(crate) fn len(&self)->usize {
+        self.0.0.lock().unwrap().heap.len()
+    }
+
+    /// stops the thread, and drops all the jobs not run yet.
+    pub(crate) fn close(&self) {
+        let (thread,heap) = {
+            let mut state = self.0.0.lock().unwrap();
+            state.closed = true;
+            self.0.1.notify_one();
+            (state.thread.take(), std::mem::take(&mut state.heap))
+        };
+        // dropped out of the lock, for the jobs may schedule again when dropped.
+        drop(heap);
+        if let Some(thread) = thread
+            && thread.thread().id() != thread::current().id() {
+            let _ = thread.join();
+        }
+    }
+
+    fn run(&self) {
+        debug!("timer thread starts ok.");
+        ON_TIMER.set(true);
+        let mut state = self.0.0.lock().unwrap();
+        loop {
+            if state.closed {
+                break;
+            }
+            let now = Instant::now();
+            let Some(at) = state.heap.peek().map(|e|e.at) else {
+                state = self.0.1.wait(state).unwrap();
+                continue;
+            };
+            if at > now {
+                state = self.0.1.wait_timeout(state, at-now).unwrap().0;
+                continue;
+            }
+            let Some(Entry { job, .. }) = state.heap.pop() else {
+                continue;
+            };
+            drop(state);
+            if let Err(_e) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                error!("timer job panicked: {}", crate::fault::panic_message(&*_e));
+            }
+            state = self.0.0.lock().unwrap();
+        }
+        debug!("timer thread exited normally.");
+    }
+}
+
+#[test]
+fn test_timer() {
+    use std::{sync::mpsc, time::Duration};
+    let timer = Timer::new();
+    let (tx,rx) = mpsc::channel();
+    let now = Instant::now();
+    for (i,ms) in [30u64,10,20,10].into_iter().enumerate() {
+        let tx = tx.clone();
+        timer.schedule(now+Duration::from_millis(ms), Box::new(move||tx.send(i).unwrap()));
+    }
+    let order: Vec<_> = (0..4).map(|_|rx.recv().unwrap()).collect();
+    assert_eq!(order, vec![1,3,2,0]);
+    assert!(now.elapsed() >= Duration::from_millis(30));
+
+    timer.schedule(now+Duration::from_secs(3600), Box::new(move||tx.send(9).unwrap()));
+    assert_eq!(timer.len(), 1);
+    timer.close();
+    assert!(rx.recv().is_err());
+    assert!(!timer.schedule(now, Box::new(||{})));
+}